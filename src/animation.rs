@@ -0,0 +1,105 @@
+use serde::Deserialize;
+
+// How long the frames of an animation are shown, in seconds.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum Duration {
+	// every frame as long.
+	All(f32),
+	// one duration per frame.
+	Each(Vec<f32>),
+}
+
+// Cells of the tile sheet shown one after another, used by animated tiles and the player.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Animation {
+	pub name: String,
+	// [column, row] of every frame in the tile sheet.
+	pub frames: Vec<(i32, i32)>,
+	pub duration: Duration,
+	// start over after the last frame, or keep showing it.
+	#[serde(default = "looped", rename = "loop")]
+	pub looped: bool,
+}
+
+fn looped() -> bool {
+	true
+}
+
+impl Animation {
+
+	// check the frames and durations fit together.
+	pub fn check(&self) -> Result<(), String> {
+		if self.frames.is_empty() {
+			return Err(format!("animation '{}' has no frames", self.name));
+		}
+		if let Duration::Each(durations) = &self.duration {
+			if durations.len() != self.frames.len() {
+				return Err(format!("animation '{}' has {} frames but {} durations", self.name, self.frames.len(), durations.len()));
+			}
+		}
+		if (0..self.frames.len()).any(|index| self.frame_duration(index) <= 0.0) {
+			return Err(format!("animation '{}' has a frame that isn't shown, durations must be positive", self.name));
+		}
+		Ok(())
+	}
+
+	fn frame_duration(&self, index: usize) -> f32 {
+		match &self.duration {
+			Duration::All(duration) => *duration,
+			Duration::Each(durations) => durations[index],
+		}
+	}
+
+	// the cell shown `time` seconds after the animation started.
+	pub fn frame(&self, time: f32) -> (i32, i32) {
+		let total: f32 = (0..self.frames.len()).map(|index| self.frame_duration(index)).sum();
+		let mut time = if self.looped { time.rem_euclid(total) } else { time };
+		for (index, &frame) in self.frames.iter().enumerate() {
+			time -= self.frame_duration(index);
+			if time < 0.0 {
+				return frame;
+			}
+		}
+		self.frames[self.frames.len() - 1]
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn animation(duration: Duration, looped: bool) -> Animation {
+		Animation {
+			name: String::from("fire"),
+			frames: vec![(1, 7), (2, 7), (3, 7)],
+			duration,
+			looped,
+		}
+	}
+
+	#[test]
+	fn test_frames() {
+		let fire = animation(Duration::All(0.1), true);
+		assert_eq!(fire.frame(0.0), (1, 7));
+		assert_eq!(fire.frame(0.15), (2, 7));
+		assert_eq!(fire.frame(0.25), (3, 7));
+		assert_eq!(fire.frame(0.35), (1, 7));
+
+		let once = animation(Duration::Each(vec![0.5, 0.1, 0.1]), false);
+		assert_eq!(once.frame(0.55), (2, 7));
+		assert_eq!(once.frame(10.0), (3, 7));
+	}
+
+	#[test]
+	fn test_check() {
+		assert!(animation(Duration::All(0.1), true).check().is_ok());
+		assert!(animation(Duration::All(0.0), true).check().is_err());
+		assert!(animation(Duration::Each(vec![0.1, 0.1]), true).check().is_err());
+
+		let mut empty = animation(Duration::All(0.1), true);
+		empty.frames.clear();
+		assert!(empty.check().is_err());
+	}
+}
@@ -0,0 +1,186 @@
+use crate::player::Player;
+use crate::save::Settings;
+use crate::world::Event;
+
+use macroquad::audio::{load_sound, play_sound, set_sound_volume, stop_sound, PlaySoundParams, Sound};
+
+use std::collections::HashMap;
+
+// A short sound played when something happens in the game, from sounds/<name>.wav.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Effect {
+	Coin,
+	Gravity,
+	Jump,
+	Death,
+	Portal,
+}
+
+impl Effect {
+
+	pub const ALL: [Effect; 5] = [Effect::Coin, Effect::Gravity, Effect::Jump, Effect::Death, Effect::Portal];
+
+	pub fn name(self) -> &'static str {
+		match self {
+			Effect::Coin => "coin",
+			Effect::Gravity => "gravity",
+			Effect::Jump => "jump",
+			Effect::Death => "death",
+			Effect::Portal => "portal",
+		}
+	}
+
+	/*
+	 * The effects of one tick, from the events its commands raised and how the player changed
+	 * during it. A tick that killed the player, restarted the map or went through a portal moved
+	 * the player without it walking there, so only that event is heard then.
+	 * */
+	pub fn of_tick(before: &Player, after: &Player, events: &[Event]) -> Vec<Effect> {
+		let mut effects = Vec::new();
+		for event in events {
+			match event {
+				Event::Died { .. } => effects.push(Effect::Death),
+				Event::EnteredPortal { .. } | Event::Won { .. } => effects.push(Effect::Portal),
+				_ => {}
+			}
+		}
+		if events.iter().any(|event| matches!(event, Event::Died { .. } | Event::Respawned | Event::EnteredPortal { .. } | Event::Won { .. } | Event::Reset)) {
+			return effects;
+		}
+
+		if after.coins() > before.coins() {
+			effects.push(Effect::Coin);
+		}
+		if after.has_gravity() != before.has_gravity() {
+			effects.push(Effect::Gravity);
+		} else if after.has_gravity() && before.vy() >= 0.0 && after.vy() < 0.0 {
+			// left the ground, by jumping or bouncing off an enemy.
+			effects.push(Effect::Jump);
+		}
+		effects
+	}
+}
+
+/*
+ * Plays the sound effects and the music looped while a map is played. Sounds that can't be
+ * loaded are reported once and left silent, the game plays on without them.
+ * */
+pub struct Audio {
+	effects: HashMap<Effect, Sound>,
+	// every music file loaded so far by name, macroquad can't free sounds so they are kept.
+	tracks: HashMap<String, Sound>,
+	// the music file playing.
+	playing: Option<String>,
+	music_volume: f32,
+	effects_volume: f32,
+}
+
+impl Audio {
+
+	pub const SOUNDS_DIR: &'static str = "sounds";
+	pub const MUSIC_DIR: &'static str = "music";
+
+	pub async fn load(settings: &Settings) -> Self {
+		let mut effects = HashMap::new();
+		for effect in Effect::ALL {
+			let path = format!("{}/{}.wav", Self::SOUNDS_DIR, effect.name());
+			if let Some(sound) = load(&path).await {
+				effects.insert(effect, sound);
+			}
+		}
+		Audio {
+			effects,
+			tracks: HashMap::new(),
+			playing: None,
+			music_volume: settings.music_volume,
+			effects_volume: settings.effects_volume,
+		}
+	}
+
+	pub fn play(&self, effect: Effect) {
+		if let Some(&sound) = self.effects.get(&effect) {
+			play_sound(sound, PlaySoundParams { looped: false, volume: self.effects_volume });
+		}
+	}
+
+	// loop `music` from music/, or stop the music for None. Music already playing carries on.
+	pub async fn play_music(&mut self, music: Option<&str>) {
+		if self.playing.as_deref() == music {
+			return;
+		}
+		if let Some(sound) = self.playing.take().and_then(|playing| self.tracks.get(&playing)) {
+			stop_sound(*sound);
+		}
+		let Some(music) = music else {
+			return;
+		};
+		if !self.tracks.contains_key(music) {
+			let Some(sound) = load(&format!("{}/{}", Self::MUSIC_DIR, music)).await else {
+				// don't try again every frame.
+				self.playing = Some(music.to_string());
+				return;
+			};
+			self.tracks.insert(music.to_string(), sound);
+		}
+		play_sound(self.tracks[music], PlaySoundParams { looped: true, volume: self.music_volume });
+		self.playing = Some(music.to_string());
+	}
+
+	pub fn set_volumes(&mut self, settings: &Settings) {
+		self.music_volume = settings.music_volume;
+		self.effects_volume = settings.effects_volume;
+		if let Some(sound) = self.playing.as_ref().and_then(|playing| self.tracks.get(playing)) {
+			set_sound_volume(*sound, self.music_volume);
+		}
+	}
+}
+
+// load a WAV or Ogg file, the decoder panics on anything else so other files are refused first.
+async fn load(path: &str) -> Option<Sound> {
+	let result = match std::fs::read(path) {
+		Ok(data) if data.starts_with(b"RIFF") || data.starts_with(b"OggS") => load_sound(path).await.map_err(|err| err.to_string()),
+		Ok(_) => Err(String::from("not a WAV or Ogg file")),
+		Err(err) => Err(err.to_string()),
+	};
+	match result {
+		Ok(sound) => Some(sound),
+		Err(err) => {
+			eprintln!("Could not load sound {}: {}", path, err);
+			None
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_effects_of_tick() {
+		let before = Player::new();
+
+		let mut after = before.clone();
+		after.add_coins(1);
+		after.vy = -2.0;
+		let coin = Event::Removed { tile: 'c', x: 64.0, y: 32.0 };
+		assert_eq!(Effect::of_tick(&before, &after, &[coin]), [Effect::Coin, Effect::Jump]);
+
+		let mut after = before.clone();
+		after.gravity_toggle();
+		assert_eq!(Effect::of_tick(&before, &after, &[]), [Effect::Gravity]);
+
+		// rising in zero gravity isn't a jump.
+		let mut floating = before.clone();
+		floating.gravity_toggle();
+		floating.vy = 0.0;
+		let mut after = floating.clone();
+		after.vy = -6.0;
+		assert!(Effect::of_tick(&floating, &after, &[]).is_empty());
+
+		// dying puts the player back with gravity on, that doesn't toggle it.
+		let died = Event::Died { tile: 'f', tile_x: 3, tile_y: 4 };
+		assert_eq!(Effect::of_tick(&floating, &before, &[died]), [Effect::Death]);
+		let portal = Event::EnteredPortal { portal: 'p', map: String::from("map2.txt") };
+		assert_eq!(Effect::of_tick(&before, &before, &[portal, Event::Reset]), [Effect::Portal]);
+	}
+}
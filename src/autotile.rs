@@ -0,0 +1,160 @@
+use serde::Deserialize;
+
+// Neighbour bits of a tile, clockwise from the top.
+pub const N: u8 = 1;
+pub const NE: u8 = 2;
+pub const E: u8 = 4;
+pub const SE: u8 = 8;
+pub const S: u8 = 16;
+pub const SW: u8 = 32;
+pub const W: u8 = 64;
+pub const NW: u8 = 128;
+
+// Bits of the 3x3 pattern read row by row, the middle is the tile itself.
+const PATTERN_BITS: [Option<u8>; 9] = [
+	Some(NW), Some(N), Some(NE),
+	Some(W), None, Some(E),
+	Some(SW), Some(S), Some(SE),
+];
+
+// A sprite cell of the tile sheet.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TileSprite {
+	pub cell: (i32, i32),
+	pub rotation: f32,
+}
+
+// One rule of an autotile set. The pattern is three rows of three characters around the tile:
+// '#' a neighbour of the same set, '.' anything else and '?' either.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AutotileRule {
+	pub pattern: Vec<String>,
+	pub sprite: (i32, i32),
+	#[serde(default)]
+	pub rotation: f32,
+	#[serde(skip)]
+	set: u8,
+	#[serde(skip)]
+	clear: u8,
+}
+
+impl AutotileRule {
+
+	pub fn matches(&self, mask: u8) -> bool {
+		mask & self.set == self.set && mask & self.clear == 0
+	}
+
+	// turn the pattern rows into the bits that must be set and clear.
+	fn compile(&mut self) -> Result<(), String> {
+		let cells: Vec<char> = self.pattern.iter().flat_map(|row| row.chars()).collect();
+		if self.pattern.len() != 3 || self.pattern.iter().any(|row| row.chars().count() != 3) {
+			return Err(format!("pattern {:?} must be three rows of three characters", self.pattern));
+		}
+
+		self.set = 0;
+		self.clear = 0;
+		for (cell, bit) in cells.iter().zip(PATTERN_BITS) {
+			match (cell, bit) {
+				(_, None) => {}
+				('#', Some(bit)) => self.set |= bit,
+				('.', Some(bit)) => self.clear |= bit,
+				('?', Some(_)) => {}
+				(other, _) => return Err(format!("unknown character '{}' in pattern {:?}", other, self.pattern)),
+			}
+		}
+		Ok(())
+	}
+}
+
+// A named list of rules, the first rule matching the neighbours of a tile picks its sprite.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Autotile {
+	pub name: String,
+	pub rule: Vec<AutotileRule>,
+}
+
+impl Autotile {
+
+	pub fn compile(&mut self) -> Result<(), String> {
+		for rule in &mut self.rule {
+			rule.compile().map_err(|reason| format!("autotile '{}': {}", self.name, reason))?;
+		}
+		Ok(())
+	}
+
+	pub fn pick(&self, mask: u8) -> Option<TileSprite> {
+		self.rule.iter()
+			.find(|rule| rule.matches(mask))
+			.map(|rule| TileSprite { cell: rule.sprite, rotation: rule.rotation })
+	}
+}
+
+// Builds the neighbour mask of a tile from `same(dx, dy)`. A corner only counts when both sides
+// next to it do too, which leaves the 47 shapes of a blob tile set.
+pub fn neighbour_mask(same: impl Fn(i64, i64) -> bool) -> u8 {
+	let mut mask = 0;
+	for (dx, dy, bit) in [(0, -1, N), (1, 0, E), (0, 1, S), (-1, 0, W)] {
+		if same(dx, dy) {
+			mask |= bit;
+		}
+	}
+	for (dx, dy, bit, sides) in [(1, -1, NE, N | E), (1, 1, SE, S | E), (-1, 1, SW, S | W), (-1, -1, NW, N | W)] {
+		if mask & sides == sides && same(dx, dy) {
+			mask |= bit;
+		}
+	}
+	mask
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn rule(pattern: [&str; 3], sprite: (i32, i32)) -> AutotileRule {
+		let mut rule = AutotileRule {
+			pattern: pattern.iter().map(|row| row.to_string()).collect(),
+			sprite,
+			rotation: 0.0,
+			set: 0,
+			clear: 0,
+		};
+		rule.compile().unwrap();
+		rule
+	}
+
+	#[test]
+	fn test_first_matching_rule_wins() {
+		let autotile = Autotile {
+			name: String::from("wall"),
+			rule: vec![
+				rule(["?.?", "#x#", "???"], (1, 0)),
+				rule(["###", "#x#", "###"], (2, 0)),
+				rule(["???", "?x?", "???"], (3, 0)),
+			],
+		};
+		assert_eq!(autotile.pick(E | W).unwrap().cell, (1, 0));
+		assert_eq!(autotile.pick(0xff).unwrap().cell, (2, 0));
+		assert_eq!(autotile.pick(N | E | S | W).unwrap().cell, (3, 0));
+	}
+
+	#[test]
+	fn test_corners_need_both_sides() {
+		// a diagonal neighbour alone doesn't count.
+		let mask = neighbour_mask(|dx, dy| (dx, dy) == (1, -1) || (dx, dy) == (0, -1));
+		assert_eq!(mask, N);
+
+		let mask = neighbour_mask(|dx, dy| matches!((dx, dy), (1, -1) | (0, -1) | (1, 0)));
+		assert_eq!(mask, N | NE | E);
+	}
+
+	#[test]
+	fn test_bad_pattern() {
+		let mut bad = rule(["???", "?x?", "???"], (0, 0));
+		bad.pattern = vec![String::from("??"), String::from("?x?"), String::from("???")];
+		assert!(bad.compile().is_err());
+		bad.pattern = vec![String::from("?z?"), String::from("?x?"), String::from("???")];
+		assert!(bad.compile().is_err());
+	}
+}
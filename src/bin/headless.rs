@@ -0,0 +1,147 @@
+use serde::Serialize;
+
+use xrunner::replay::Replay;
+use xrunner::tiles::TileSet;
+use xrunner::world::{Event, World};
+
+use std::process::ExitCode;
+use std::rc::Rc;
+
+const USAGE: &str = "usage: headless [MAP] [--replay FILE] [--script 'TICKS*KEYS ...'] [--ticks N]
+
+Plays a map without opening a window until the player leaves it through a portal, dies or the
+input runs out, then prints what happened as JSON. MAP is a file in maps/, it defaults to the map
+of the replay. Keys in scripts are jump, left, right, up, down, reset or none, joined with '+'.";
+
+// How the run on the map ended.
+#[derive(Serialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+enum Outcome {
+	// went through `portal` to the map `target`.
+	Portal { portal: char, target: String },
+	// `portal` leads to a map that doesn't exist, the game is won.
+	Won { portal: char, target: String },
+	Died { tile: char, tile_x: usize, tile_y: usize },
+	// the input ran out before anything happened.
+	Timeout,
+	Error { message: String },
+}
+
+#[derive(Serialize)]
+struct Report {
+	map: String,
+	#[serde(flatten)]
+	outcome: Outcome,
+	coins: i32,
+	ticks: usize,
+	position: (f32, f32),
+}
+
+struct Args {
+	map: Option<String>,
+	replay: Option<String>,
+	script: Option<String>,
+	ticks: Option<usize>,
+}
+
+fn parse_args() -> Result<Args, String> {
+	let mut parsed = Args { map: None, replay: None, script: None, ticks: None };
+	let mut args = std::env::args().skip(1);
+	while let Some(arg) = args.next() {
+		let mut value = |name: &str| args.next().ok_or(format!("{} needs a value", name));
+		match arg.as_str() {
+			"--replay" => parsed.replay = Some(value("--replay")?),
+			"--script" => parsed.script = Some(value("--script")?),
+			"--ticks" => {
+				let ticks = value("--ticks")?;
+				parsed.ticks = Some(ticks.parse().map_err(|_| format!("--ticks '{}' is not a number", ticks))?);
+			}
+			"--help" | "-h" => return Err(String::new()),
+			_ if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+			_ => parsed.map = Some(arg),
+		}
+	}
+	Ok(parsed)
+}
+
+// the inputs to play, from a replay file, a script or none at all.
+fn load_inputs(args: &Args) -> Result<Replay, String> {
+	match (&args.replay, &args.script) {
+		(Some(_), Some(_)) => Err(String::from("use either --replay or --script")),
+		(Some(path), None) => Replay::load(path).map_err(|err| err.to_string()),
+		(None, Some(script)) => Replay::from_script(args.map.as_deref().unwrap_or_default(), script),
+		(None, None) => Ok(Replay::new(args.map.as_deref().unwrap_or_default(), 0)),
+	}
+}
+
+fn run(map: &str, inputs: &Replay, ticks: usize) -> Report {
+	let mut report = Report {
+		map: map.to_string(),
+		outcome: Outcome::Timeout,
+		coins: 0,
+		ticks: 0,
+		position: (0.0, 0.0),
+	};
+
+	let world = TileSet::from_file(TileSet::PATH).map_err(|err| err.to_string())
+		.and_then(|tile_set| World::load(map, Rc::new(tile_set)).map_err(|err| err.to_string()));
+	let mut world = match world {
+		Ok(world) => world,
+		Err(err) => {
+			report.outcome = Outcome::Error { message: err };
+			return report;
+		}
+	};
+
+	'ticks: for tick in 0..ticks {
+		report.ticks = tick + 1;
+		for event in world.tick(inputs.input(tick).unwrap_or_default(), Vec::new()) {
+			let outcome = match event {
+				Event::EnteredPortal { portal, map } => Outcome::Portal { portal, target: map },
+				Event::Won { portal, map } => Outcome::Won { portal, target: map },
+				Event::Died { tile, tile_x, tile_y } => Outcome::Died { tile, tile_x, tile_y },
+				Event::Failed(err, _) => Outcome::Error { message: err.to_string() },
+				Event::Respawned | Event::Checkpoint { .. } | Event::Reset | Event::Exit | Event::Removed { .. } => continue,
+			};
+			report.outcome = outcome;
+			break 'ticks;
+		}
+	}
+
+	report.coins = world.player.coins();
+	report.position = (world.player.x, world.player.y);
+	report
+}
+
+fn main() -> ExitCode {
+	let args = match parse_args() {
+		Ok(args) => args,
+		Err(err) => {
+			if !err.is_empty() {
+				eprintln!("{}", err);
+			}
+			eprintln!("{}", USAGE);
+			return ExitCode::from(2);
+		}
+	};
+	let inputs = match load_inputs(&args) {
+		Ok(inputs) => inputs,
+		Err(err) => {
+			eprintln!("{}", err);
+			return ExitCode::from(2);
+		}
+	};
+	let map = args.map.clone().unwrap_or(inputs.map.clone());
+	if map.is_empty() {
+		eprintln!("{}", USAGE);
+		return ExitCode::from(2);
+	}
+
+	let report = run(&map, &inputs, args.ticks.unwrap_or(inputs.len()));
+	println!("{}", serde_json::to_string(&report).unwrap());
+
+	match report.outcome {
+		Outcome::Error { .. } => ExitCode::FAILURE,
+		_ => ExitCode::SUCCESS,
+	}
+}
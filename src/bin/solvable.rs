@@ -0,0 +1,138 @@
+use xrunner::entity::Entities;
+use xrunner::map::Map;
+use xrunner::player::Player;
+use xrunner::replay::Replay;
+use xrunner::solver::{self, Target};
+use xrunner::tiles::{TileSet, Touch};
+use xrunner::world::map_file;
+
+use std::process::ExitCode;
+use std::rc::Rc;
+
+const USAGE: &str = "usage: solvable MAP [--from PORTAL] [--paths] [--max-states N]
+
+Searches which portals, coins and gravity pickups of a map in maps/ the player can reach, using
+the real movement rules. The player starts where a new game starts, or on the portal PORTAL as
+when arriving from another map. With --paths the shortest input found to every target is printed
+as a script for `headless --script`. Exits with 1 if anything can't be reached.";
+
+struct Args {
+	map: String,
+	from: Option<char>,
+	paths: bool,
+	max_states: usize,
+}
+
+fn parse_args() -> Result<Args, String> {
+	let mut map = None;
+	let mut parsed = Args { map: String::new(), from: None, paths: false, max_states: 2_000_000 };
+	let mut args = std::env::args().skip(1);
+	while let Some(arg) = args.next() {
+		let mut value = |name: &str| args.next().ok_or(format!("{} needs a value", name));
+		match arg.as_str() {
+			"--from" => {
+				let portal = value("--from")?;
+				let mut chars = portal.chars();
+				match (chars.next(), chars.next()) {
+					(Some(portal), None) => parsed.from = Some(portal),
+					_ => return Err(format!("--from '{}' is not a single character", portal)),
+				}
+			}
+			"--paths" => parsed.paths = true,
+			"--max-states" => {
+				let max_states = value("--max-states")?;
+				parsed.max_states = max_states.parse().map_err(|_| format!("--max-states '{}' is not a number", max_states))?;
+			}
+			"--help" | "-h" => return Err(String::new()),
+			_ if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+			_ => map = Some(arg),
+		}
+	}
+	parsed.map = map.ok_or(String::from("no map given"))?;
+	Ok(parsed)
+}
+
+fn kind(touch: Touch) -> &'static str {
+	match touch {
+		Touch::Collect => "coin",
+		Touch::Gravity => "gravity",
+		Touch::Portal => "portal",
+		Touch::Kill => "hazard",
+		Touch::Checkpoint => "checkpoint",
+	}
+}
+
+fn describe(target: &Target) -> String {
+	format!("{:<11}'{}' at column {}, row {}", kind(target.touch), target.tile, target.tile_x + 1, target.tile_y + 1)
+}
+
+fn main() -> ExitCode {
+	let args = match parse_args() {
+		Ok(args) => args,
+		Err(err) => {
+			if !err.is_empty() {
+				eprintln!("{}", err);
+			}
+			eprintln!("{}", USAGE);
+			return ExitCode::from(2);
+		}
+	};
+
+	let map = TileSet::from_file(TileSet::PATH).map_err(|err| err.to_string())
+		.and_then(|tile_set| Map::from_file(&map_file(&args.map), Rc::new(tile_set)).map_err(|err| err.to_string()));
+	let mut map = match map {
+		Ok(map) => map,
+		Err(err) => {
+			eprintln!("{}", err);
+			return ExitCode::FAILURE;
+		}
+	};
+	let entities = Entities::spawn(&mut map);
+
+	let mut player = Player::new();
+	if let Some(portal) = args.from {
+		match entities.find(portal) {
+			Some(spawn) => {
+				player.set_spawn_pos(spawn);
+				player.reset();
+			}
+			None => {
+				eprintln!("{} has no portal '{}'", args.map, portal);
+				return ExitCode::FAILURE;
+			}
+		}
+	}
+
+	let reachability = solver::search(&map, &entities, player, args.max_states);
+	let reached = reachability.targets.iter().filter(|target| target.path.is_some()).count();
+	println!(
+		"{}: {} of {} targets reachable, {} states searched{}",
+		args.map,
+		reached,
+		reachability.targets.len(),
+		reachability.states,
+		if reachability.complete { "" } else { " (stopped at --max-states, the rest may be reachable)" },
+	);
+
+	for target in &reachability.targets {
+		match &target.path {
+			Some(path) => {
+				println!("  {}  reachable in {} ticks", describe(target), path.len());
+				if args.paths {
+					let mut replay = Replay::new(&args.map, 0);
+					for &input in path {
+						replay.record(input);
+					}
+					println!("      {}", replay.script());
+				}
+			}
+			None => println!("  {}  UNREACHABLE", describe(target)),
+		}
+	}
+
+	if reached == reachability.targets.len() {
+		ExitCode::SUCCESS
+	} else {
+		ExitCode::FAILURE
+	}
+}
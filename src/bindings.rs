@@ -0,0 +1,411 @@
+use crate::config::ConfigError;
+use crate::gamepad::Gamepads;
+use crate::input::Input;
+
+use gilrs::{Axis, Button};
+use macroquad::prelude::*;
+
+use std::fmt;
+use std::path::Path;
+
+// Something the player can do, bound to any number of keys, gamepad buttons and sticks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+	Left,
+	Right,
+	Up,
+	Down,
+	Jump,
+	Reset,
+	Pause,
+}
+
+impl Action {
+
+	pub const ALL: [Action; 7] = [Action::Left, Action::Right, Action::Up, Action::Down, Action::Jump, Action::Reset, Action::Pause];
+
+	// the name in bindings.toml.
+	pub fn name(self) -> &'static str {
+		match self {
+			Action::Left => "left",
+			Action::Right => "right",
+			Action::Up => "up",
+			Action::Down => "down",
+			Action::Jump => "jump",
+			Action::Reset => "reset",
+			Action::Pause => "pause",
+		}
+	}
+
+	// the bit held down in the `Input` of a tick, None for actions outside the simulation.
+	pub fn bit(self) -> Option<u8> {
+		match self {
+			Action::Left => Some(Input::LEFT),
+			Action::Right => Some(Input::RIGHT),
+			Action::Up => Some(Input::UP),
+			Action::Down => Some(Input::DOWN),
+			Action::Jump => Some(Input::JUMP),
+			Action::Reset => Some(Input::RESET),
+			Action::Pause => None,
+		}
+	}
+}
+
+// One key, gamepad button or stick direction, written as "key:Space", "button:South" or "axis:LeftStickX-".
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Binding {
+	Key(KeyCode),
+	Button(Button),
+	// a stick pushed past `Gamepads::STICK_THRESHOLD`, 1 towards right or up and -1 towards left or down.
+	Axis(Axis, i8),
+}
+
+impl Binding {
+
+	// keys that can be bound, by their name in bindings.toml.
+	const KEYS: [KeyCode; 120] = [
+		KeyCode::Space, KeyCode::Apostrophe, KeyCode::Comma, KeyCode::Minus, KeyCode::Period, KeyCode::Slash,
+		KeyCode::Key0, KeyCode::Key1, KeyCode::Key2, KeyCode::Key3, KeyCode::Key4,
+		KeyCode::Key5, KeyCode::Key6, KeyCode::Key7, KeyCode::Key8, KeyCode::Key9,
+		KeyCode::Semicolon, KeyCode::Equal,
+		KeyCode::A, KeyCode::B, KeyCode::C, KeyCode::D, KeyCode::E, KeyCode::F, KeyCode::G, KeyCode::H, KeyCode::I,
+		KeyCode::J, KeyCode::K, KeyCode::L, KeyCode::M, KeyCode::N, KeyCode::O, KeyCode::P, KeyCode::Q, KeyCode::R,
+		KeyCode::S, KeyCode::T, KeyCode::U, KeyCode::V, KeyCode::W, KeyCode::X, KeyCode::Y, KeyCode::Z,
+		KeyCode::LeftBracket, KeyCode::Backslash, KeyCode::RightBracket, KeyCode::GraveAccent, KeyCode::World1, KeyCode::World2,
+		KeyCode::Escape, KeyCode::Enter, KeyCode::Tab, KeyCode::Backspace, KeyCode::Insert, KeyCode::Delete,
+		KeyCode::Right, KeyCode::Left, KeyCode::Down, KeyCode::Up,
+		KeyCode::PageUp, KeyCode::PageDown, KeyCode::Home, KeyCode::End,
+		KeyCode::CapsLock, KeyCode::ScrollLock, KeyCode::NumLock, KeyCode::PrintScreen, KeyCode::Pause,
+		KeyCode::F1, KeyCode::F2, KeyCode::F3, KeyCode::F4, KeyCode::F5, KeyCode::F6, KeyCode::F7, KeyCode::F8, KeyCode::F9,
+		KeyCode::F10, KeyCode::F11, KeyCode::F12, KeyCode::F13, KeyCode::F14, KeyCode::F15, KeyCode::F16, KeyCode::F17,
+		KeyCode::F18, KeyCode::F19, KeyCode::F20, KeyCode::F21, KeyCode::F22, KeyCode::F23, KeyCode::F24, KeyCode::F25,
+		KeyCode::Kp0, KeyCode::Kp1, KeyCode::Kp2, KeyCode::Kp3, KeyCode::Kp4,
+		KeyCode::Kp5, KeyCode::Kp6, KeyCode::Kp7, KeyCode::Kp8, KeyCode::Kp9,
+		KeyCode::KpDecimal, KeyCode::KpDivide, KeyCode::KpMultiply, KeyCode::KpSubtract, KeyCode::KpAdd,
+		KeyCode::KpEnter, KeyCode::KpEqual,
+		KeyCode::LeftShift, KeyCode::LeftControl, KeyCode::LeftAlt, KeyCode::LeftSuper,
+		KeyCode::RightShift, KeyCode::RightControl, KeyCode::RightAlt, KeyCode::RightSuper, KeyCode::Menu,
+	];
+
+	const BUTTONS: [Button; 17] = [
+		Button::South, Button::East, Button::North, Button::West,
+		Button::LeftTrigger, Button::LeftTrigger2, Button::RightTrigger, Button::RightTrigger2,
+		Button::Select, Button::Start, Button::Mode, Button::LeftThumb, Button::RightThumb,
+		Button::DPadUp, Button::DPadDown, Button::DPadLeft, Button::DPadRight,
+	];
+
+	const AXES: [Axis; 4] = [Axis::LeftStickX, Axis::LeftStickY, Axis::RightStickX, Axis::RightStickY];
+
+	pub fn parse(text: &str) -> Result<Self, String> {
+		let unknown = || format!("unknown binding '{}', expected 'key:<key>', 'button:<button>' or 'axis:<stick>+' or '-'", text);
+		match text.split_once(':').ok_or_else(unknown)? {
+			("key", name) => Self::KEYS.iter()
+				.find(|key| format!("{:?}", key) == name)
+				.map(|&key| Binding::Key(key))
+				.ok_or_else(|| format!("unknown key '{}'", name)),
+			("button", name) => Self::BUTTONS.iter()
+				.find(|button| format!("{:?}", button) == name)
+				.map(|&button| Binding::Button(button))
+				.ok_or_else(|| format!("unknown gamepad button '{}'", name)),
+			("axis", name) => {
+				let (name, direction) = match (name.strip_suffix('+'), name.strip_suffix('-')) {
+					(Some(name), _) => (name, 1),
+					(_, Some(name)) => (name, -1),
+					_ => return Err(format!("axis '{}' needs a direction, '+' or '-'", name)),
+				};
+				Self::AXES.iter()
+					.find(|axis| format!("{:?}", axis) == name)
+					.map(|&axis| Binding::Axis(axis, direction))
+					.ok_or_else(|| format!("unknown gamepad stick '{}'", name))
+			}
+			_ => Err(unknown()),
+		}
+	}
+
+	// the key, button or stick pushed this frame, for rebinding. Sticks come first as pushing the
+	// left stick up or down also presses the d-pad.
+	pub fn capture(gamepads: &Gamepads) -> Option<Self> {
+		if let Some((axis, direction)) = gamepads.first_pushed().filter(|(axis, _)| Self::AXES.contains(axis)) {
+			return Some(Binding::Axis(axis, direction));
+		}
+		if let Some(button) = gamepads.first_pressed().filter(|button| Self::BUTTONS.contains(button)) {
+			return Some(Binding::Button(button));
+		}
+		get_last_key_pressed().filter(|key| Self::KEYS.contains(key)).map(Binding::Key)
+	}
+
+	pub fn is_down(self, gamepads: &Gamepads) -> bool {
+		match self {
+			Binding::Key(key) => is_key_down(key),
+			Binding::Button(button) => gamepads.is_down(button),
+			Binding::Axis(axis, direction) => gamepads.axis(axis) * direction as f32 > Gamepads::STICK_THRESHOLD,
+		}
+	}
+
+	// true if the binding went down this frame.
+	pub fn is_pressed(self, gamepads: &Gamepads) -> bool {
+		match self {
+			Binding::Key(key) => is_key_pressed(key),
+			Binding::Button(button) => gamepads.is_pressed(button),
+			Binding::Axis(axis, direction) => gamepads.is_pushed(axis, direction),
+		}
+	}
+
+	// the name shown to the player, e.g. "Space" or "LeftStickX-".
+	pub fn label(self) -> String {
+		match self {
+			Binding::Key(key) => format!("{:?}", key),
+			Binding::Button(button) => format!("{:?}", button),
+			Binding::Axis(axis, direction) => format!("{:?}{}", axis, if direction > 0 { '+' } else { '-' }),
+		}
+	}
+}
+
+impl fmt::Display for Binding {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Binding::Key(_) => write!(f, "key:{}", self.label()),
+			Binding::Button(_) => write!(f, "button:{}", self.label()),
+			Binding::Axis(..) => write!(f, "axis:{}", self.label()),
+		}
+	}
+}
+
+/*
+ * The keys, gamepad buttons and sticks bound to every action, loaded from bindings.toml with the
+ * changes made on the settings screen read over it from saves/. Actions missing from a file keep
+ * the bindings they had:
+ *
+ *   jump = ["key:Space", "button:South"]
+ *   left = ["key:Left", "button:DPadLeft", "axis:LeftStickX-"]
+ * */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bindings {
+	// the bindings of every action, in the order of `Action::ALL`.
+	actions: Vec<Vec<Binding>>,
+}
+
+impl Default for Bindings {
+	fn default() -> Self {
+		let actions = Action::ALL.iter().map(|action| match action {
+			Action::Left => vec![Binding::Key(KeyCode::Left), Binding::Button(Button::DPadLeft), Binding::Axis(Axis::LeftStickX, -1)],
+			Action::Right => vec![Binding::Key(KeyCode::Right), Binding::Button(Button::DPadRight), Binding::Axis(Axis::LeftStickX, 1)],
+			Action::Up => vec![Binding::Key(KeyCode::Up), Binding::Button(Button::DPadUp), Binding::Axis(Axis::LeftStickY, 1)],
+			Action::Down => vec![Binding::Key(KeyCode::Down), Binding::Button(Button::DPadDown), Binding::Axis(Axis::LeftStickY, -1)],
+			Action::Jump => vec![Binding::Key(KeyCode::Space), Binding::Button(Button::South)],
+			Action::Reset => vec![Binding::Key(KeyCode::R), Binding::Button(Button::North)],
+			Action::Pause => vec![Binding::Key(KeyCode::Escape), Binding::Button(Button::Start)],
+		});
+		Bindings { actions: actions.collect() }
+	}
+}
+
+impl Bindings {
+
+	pub const PATH: &'static str = "bindings.toml";
+
+	// the player's changes, bindings.toml is only read.
+	pub const OVERRIDES_PATH: &'static str = "saves/bindings.toml";
+
+	pub fn load(path: &str) -> Result<Self, ConfigError> {
+		let content = std::fs::read_to_string(path).map_err(|err| ConfigError::MissingFile {
+			path: path.to_string(),
+			reason: err.to_string(),
+		})?;
+
+		Self::parse(&content).map_err(|reason| ConfigError::Invalid {
+			path: path.to_string(),
+			reason,
+		})
+	}
+
+	// these bindings with the actions in the file at `path` bound as it says instead.
+	pub fn load_overrides(&self, path: &str) -> Result<Self, ConfigError> {
+		let content = std::fs::read_to_string(path).map_err(|err| ConfigError::MissingFile {
+			path: path.to_string(),
+			reason: err.to_string(),
+		})?;
+
+		self.parse_over(&content).map_err(|reason| ConfigError::Invalid {
+			path: path.to_string(),
+			reason,
+		})
+	}
+
+	// Write the actions bound differently than in `defaults`, through a temporary file like the save game.
+	pub fn save_overrides(&self, defaults: &Bindings, path: &str) -> std::io::Result<()> {
+		if let Some(dir) = Path::new(path).parent() {
+			std::fs::create_dir_all(dir)?;
+		}
+		let mut content = format!("# Bindings changed on the settings screen, read over {}.\n", Self::PATH);
+		for action in Action::ALL.into_iter().filter(|&action| self.get(action) != defaults.get(action)) {
+			content.push_str(&self.line(action));
+		}
+		let temporary = format!("{}.tmp", path);
+		std::fs::write(&temporary, content)?;
+		std::fs::rename(&temporary, path)
+	}
+
+	pub fn parse(content: &str) -> Result<Self, String> {
+		Bindings::default().parse_over(content)
+	}
+
+	fn parse_over(&self, content: &str) -> Result<Self, String> {
+		let table: toml::Table = toml::from_str(content).map_err(|err| err.to_string())?;
+
+		let mut bindings = self.clone();
+		for (name, value) in table {
+			let Some(action) = Action::ALL.iter().find(|action| action.name() == name) else {
+				return Err(format!("unknown action '{}'", name));
+			};
+			let Some(values) = value.as_array() else {
+				return Err(format!("{}: expected a list of bindings", name));
+			};
+			let mut parsed = Vec::new();
+			for value in values {
+				let Some(text) = value.as_str() else {
+					return Err(format!("{}: expected a list of bindings", name));
+				};
+				parsed.push(Binding::parse(text).map_err(|reason| format!("{}: {}", name, reason))?);
+			}
+			bindings.actions[*action as usize] = parsed;
+		}
+		Ok(bindings)
+	}
+
+	pub fn get(&self, action: Action) -> &[Binding] {
+		&self.actions[action as usize]
+	}
+
+	/*
+	 * Bind `binding` to `action` in place of its bindings of the same kind, so a new key replaces
+	 * the old key and leaves the gamepad alone. No other action keeps the binding.
+	 * */
+	pub fn bind(&mut self, action: Action, binding: Binding) {
+		for bindings in &mut self.actions {
+			bindings.retain(|bound| *bound != binding);
+		}
+		let bindings = &mut self.actions[action as usize];
+		bindings.retain(|bound| std::mem::discriminant(bound) != std::mem::discriminant(&binding));
+		bindings.push(binding);
+	}
+
+	// the actions held down on the keyboard and gamepads, for one tick.
+	pub fn input(&self, gamepads: &Gamepads) -> Input {
+		let mut bits = 0;
+		for action in Action::ALL {
+			if let Some(bit) = action.bit() {
+				if self.is_down(action, gamepads) {
+					bits |= bit;
+				}
+			}
+		}
+		Input(bits)
+	}
+
+	pub fn is_down(&self, action: Action, gamepads: &Gamepads) -> bool {
+		self.get(action).iter().any(|binding| binding.is_down(gamepads))
+	}
+
+	// true if any binding of `action` went down this frame.
+	pub fn is_pressed(&self, action: Action, gamepads: &Gamepads) -> bool {
+		self.get(action).iter().any(|binding| binding.is_pressed(gamepads))
+	}
+
+	// the line of `action` in bindings.toml, e.g. "jump = [\"key:Space\", \"button:South\"]".
+	fn line(&self, action: Action) -> String {
+		let bindings: Vec<String> = self.get(action).iter().map(|binding| format!("\"{}\"", binding)).collect();
+		format!("{} = [{}]\n", action.name(), bindings.join(", "))
+	}
+
+	// the bindings of `action` to show the player, e.g. "Space, South".
+	pub fn labels(&self, action: Action) -> String {
+		let labels: Vec<String> = self.get(action).iter().map(|binding| binding.label()).collect();
+		if labels.is_empty() {
+			String::from("unbound")
+		} else {
+			labels.join(", ")
+		}
+	}
+}
+
+impl fmt::Display for Bindings {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		writeln!(f, "# The keys, gamepad buttons and sticks bound to every action, also changed on the settings screen.")?;
+		writeln!(f, "# \"key:<key>\" takes macroquad key names, \"button:<button>\" and \"axis:<stick>+\" or \"-\" gilrs names.")?;
+		for action in Action::ALL {
+			write!(f, "{}", self.line(action))?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_round_trip() {
+		let bindings = Bindings::default();
+		let text = bindings.to_string();
+		assert!(text.contains("\njump = [\"key:Space\", \"button:South\"]\n"));
+		assert!(text.contains("\nleft = [\"key:Left\", \"button:DPadLeft\", \"axis:LeftStickX-\"]\n"));
+		assert_eq!(Bindings::parse(&text), Ok(bindings));
+
+		// the shipped file holds the defaults, changes go to `OVERRIDES_PATH`.
+		assert_eq!(Bindings::load(Bindings::PATH), Ok(Bindings::default()));
+	}
+
+	#[test]
+	fn test_overrides() {
+		let dir = std::env::temp_dir().join(format!("xrunner-bindings-{}", std::process::id()));
+		let path = dir.join("bindings.toml").to_str().unwrap().to_string();
+		let defaults = Bindings::default();
+
+		// only the changed action is written and read back over the defaults.
+		let mut bindings = defaults.clone();
+		bindings.bind(Action::Jump, Binding::Key(KeyCode::Z));
+		bindings.save_overrides(&defaults, &path).unwrap();
+		let content = std::fs::read_to_string(&path).unwrap();
+		assert!(content.contains("\njump = [\"button:South\", \"key:Z\"]\n"));
+		assert!(!content.contains("left"));
+		assert_eq!(defaults.load_overrides(&path), Ok(bindings));
+		assert!(!Path::new(&format!("{}.tmp", path)).exists());
+
+		// going back to the defaults leaves nothing to read over them.
+		defaults.save_overrides(&defaults, &path).unwrap();
+		assert_eq!(defaults.load_overrides(&path), Ok(defaults));
+
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+
+	#[test]
+	fn test_parse() {
+		// actions left out keep their defaults.
+		let bindings = Bindings::parse("jump = [\"key:Z\", \"axis:RightStickY+\"]\npause = []\n").unwrap();
+		assert_eq!(bindings.get(Action::Jump), [Binding::Key(KeyCode::Z), Binding::Axis(Axis::RightStickY, 1)]);
+		assert!(bindings.get(Action::Pause).is_empty());
+		assert_eq!(bindings.labels(Action::Pause), "unbound");
+		assert_eq!(bindings.get(Action::Left), Bindings::default().get(Action::Left));
+
+		assert!(Bindings::parse("fly = [\"key:F\"]\n").is_err());
+		assert!(Bindings::parse("jump = \"key:Space\"\n").is_err());
+		assert!(Bindings::parse("jump = [\"key:Spacebar\"]\n").is_err());
+		assert!(Bindings::parse("jump = [\"axis:LeftStickX\"]\n").is_err());
+		assert!(Bindings::parse("jump = [\"Space\"]\n").is_err());
+	}
+
+	#[test]
+	fn test_bind() {
+		let mut bindings = Bindings::default();
+
+		// a new key replaces the old one, the gamepad keeps its bindings.
+		bindings.bind(Action::Jump, Binding::Key(KeyCode::Up));
+		assert_eq!(bindings.get(Action::Jump), [Binding::Button(Button::South), Binding::Key(KeyCode::Up)]);
+		assert_eq!(bindings.labels(Action::Up), "DPadUp, LeftStickY+");
+
+		bindings.bind(Action::Up, Binding::Axis(Axis::LeftStickY, -1));
+		assert_eq!(bindings.get(Action::Up), [Binding::Button(Button::DPadUp), Binding::Axis(Axis::LeftStickY, -1)]);
+		assert_eq!(bindings.get(Action::Down), [Binding::Key(KeyCode::Down), Binding::Button(Button::DPadDown)]);
+	}
+}
@@ -0,0 +1,185 @@
+use crate::map::{Aabb, Map};
+
+use macroquad::prelude::*;
+
+// Follows a target around maps that are larger than the window. The target can move freely
+// inside a dead zone in the middle of the view, outside it the camera eases after it.
+pub struct Camera {
+	// top left corner of the view in map pixels.
+	pub x: f32,
+	pub y: f32,
+	// position at the start of the last tick, used to interpolate drawing between ticks.
+	prev_x: f32,
+	prev_y: f32,
+	pub width: f32,
+	pub height: f32,
+}
+
+impl Camera {
+
+	// size of the dead zone in the middle of the view.
+	pub const DEAD_ZONE_WIDTH: f32 = 6.0 * Map::TILE_SIZE;
+	pub const DEAD_ZONE_HEIGHT: f32 = 4.0 * Map::TILE_SIZE;
+
+	// part of the remaining distance the camera covers every tick.
+	pub const SMOOTHING: f32 = 0.1;
+
+	pub fn new(width: f32, height: f32) -> Self {
+		Camera {
+			x: 0.0,
+			y: 0.0,
+			prev_x: 0.0,
+			prev_y: 0.0,
+			width,
+			height,
+		}
+	}
+
+	// ease towards the target, called once per tick.
+	pub fn update(&mut self, target: Aabb, map: &Map) {
+		self.prev_x = self.x;
+		self.prev_y = self.y;
+
+		let (goal_x, goal_y) = self.goal(target, map);
+		self.x += (goal_x - self.x) * Self::SMOOTHING;
+		self.y += (goal_y - self.y) * Self::SMOOTHING;
+
+		// don't creep forever over the last fraction of a pixel.
+		if (goal_x - self.x).abs() < 0.5 {
+			self.x = goal_x;
+		}
+		if (goal_y - self.y).abs() < 0.5 {
+			self.y = goal_y;
+		}
+	}
+
+	// jump straight to the target, e.g. after loading a map or respawning.
+	pub fn snap(&mut self, target: Aabb, map: &Map) {
+		let center_x = target.x + target.w / 2.0 - self.width / 2.0;
+		let center_y = target.y + target.h / 2.0 - self.height / 2.0;
+		self.x = Self::clamp(center_x, self.width, map.width as f32 * Map::TILE_SIZE);
+		self.y = Self::clamp(center_y, self.height, map.height as f32 * Map::TILE_SIZE);
+		self.prev_x = self.x;
+		self.prev_y = self.y;
+	}
+
+	// move the view by (dx, dy) pixels without easing, e.g. scrolling in the editor.
+	pub fn pan(&mut self, dx: f32, dy: f32, map: &Map) {
+		self.x = Self::clamp(self.x + dx, self.width, map.width as f32 * Map::TILE_SIZE);
+		self.y = Self::clamp(self.y + dy, self.height, map.height as f32 * Map::TILE_SIZE);
+		self.prev_x = self.x;
+		self.prev_y = self.y;
+	}
+
+	// the part of the map in view, `alpha` is how far we are between the previous and the current tick.
+	// Snapped to whole pixels so tiles don't get seams.
+	pub fn view(&self, alpha: f32) -> Rect {
+		let x = self.prev_x + (self.x - self.prev_x) * alpha;
+		let y = self.prev_y + (self.y - self.prev_y) * alpha;
+		Rect::new(x.round(), y.round(), self.width, self.height)
+	}
+
+	// make following draw calls use map coordinates, undo with `set_default_camera`.
+	pub fn activate(&self, alpha: f32) {
+		set_camera(&Camera2D::from_display_rect(self.view(alpha)));
+	}
+
+	// where the camera wants to be so the target is inside the dead zone.
+	fn goal(&self, target: Aabb, map: &Map) -> (f32, f32) {
+		let dead_left = self.x + (self.width - Self::DEAD_ZONE_WIDTH) / 2.0;
+		let dead_top = self.y + (self.height - Self::DEAD_ZONE_HEIGHT) / 2.0;
+
+		let mut goal_x = self.x;
+		if target.x < dead_left {
+			goal_x -= dead_left - target.x;
+		} else if target.x + target.w > dead_left + Self::DEAD_ZONE_WIDTH {
+			goal_x += target.x + target.w - (dead_left + Self::DEAD_ZONE_WIDTH);
+		}
+
+		let mut goal_y = self.y;
+		if target.y < dead_top {
+			goal_y -= dead_top - target.y;
+		} else if target.y + target.h > dead_top + Self::DEAD_ZONE_HEIGHT {
+			goal_y += target.y + target.h - (dead_top + Self::DEAD_ZONE_HEIGHT);
+		}
+
+		(
+			Self::clamp(goal_x, self.width, map.width as f32 * Map::TILE_SIZE),
+			Self::clamp(goal_y, self.height, map.height as f32 * Map::TILE_SIZE),
+		)
+	}
+
+	// keep the view inside the map, maps smaller than the view are centered.
+	fn clamp(position: f32, view: f32, map: f32) -> f32 {
+		if map <= view {
+			(map - view) / 2.0
+		} else {
+			position.clamp(0.0, map - view)
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::tiles::TileSet;
+
+	use std::rc::Rc;
+
+	fn tiles() -> Rc<TileSet> {
+		Rc::new(TileSet::from_file(TileSet::PATH).unwrap())
+	}
+
+	fn wide_map() -> Map {
+		let row = "x".repeat(100);
+		let middle = format!("x{}x", " ".repeat(98));
+		let mut rows = vec![row.clone()];
+		rows.extend(std::iter::repeat_n(middle, 48));
+		rows.push(row);
+		Map::parse_map(&rows.join("\n"), tiles()).unwrap()
+	}
+
+	fn target(x: f32, y: f32) -> Aabb {
+		Aabb { x, y, w: 32.0, h: 32.0 }
+	}
+
+	#[test]
+	fn test_snap_clamps_to_map() {
+		let map = wide_map();
+		let mut camera = Camera::new(1280.0, 640.0);
+
+		camera.snap(target(32.0, 32.0), &map);
+		assert_eq!((camera.x, camera.y), (0.0, 0.0));
+
+		camera.snap(target(3000.0, 1500.0), &map);
+		assert_eq!((camera.x, camera.y), (3200.0 - 1280.0, 1600.0 - 640.0));
+	}
+
+	#[test]
+	fn test_small_map_is_centered() {
+		let map = Map::parse_map("xxxx\nx  x\nxxxx", tiles()).unwrap();
+		let mut camera = Camera::new(1280.0, 640.0);
+		camera.snap(target(32.0, 32.0), &map);
+		assert_eq!((camera.x, camera.y), ((128.0 - 1280.0) / 2.0, (96.0 - 640.0) / 2.0));
+	}
+
+	#[test]
+	fn test_dead_zone_and_follow() {
+		let map = wide_map();
+		let mut camera = Camera::new(1280.0, 640.0);
+		camera.snap(target(1600.0, 800.0), &map);
+		let start = (camera.x, camera.y);
+
+		// small moves inside the dead zone don't move the camera.
+		camera.update(target(1650.0, 820.0), &map);
+		assert_eq!((camera.x, camera.y), start);
+
+		// leaving it to the right eases after the target until it is back at the edge.
+		for _ in 0..200 {
+			camera.update(target(2000.0, 820.0), &map);
+		}
+		let dead_right = camera.x + (camera.width + Camera::DEAD_ZONE_WIDTH) / 2.0;
+		assert_eq!(dead_right, 2032.0);
+		assert_eq!(camera.y, start.1);
+	}
+}
@@ -1,7 +1,15 @@
+use crate::entity::EntityId;
 
+#[derive(Clone, Debug, PartialEq)]
 pub enum Command {
-	RemoveEntity(char, usize, usize),
+	RemoveEntity(EntityId),
 	LoadMap(String, char),
 	Exit(),
 	ResetMap(),
+	// read the current map from its file again, e.g. to retry one that failed to load.
+	ReloadMap(),
+	// killed by the entity spawned from the character, standing on the tile (x, y).
+	Die(char, usize, usize),
+	// respawn at the checkpoint entity from now on.
+	Checkpoint(EntityId),
 }
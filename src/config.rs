@@ -0,0 +1,23 @@
+use std::fmt;
+
+// Why a file the game reads besides maps, like tiles.toml, the tile sheet or the save, couldn't be loaded.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigError {
+	MissingFile { path: String, reason: String },
+	Invalid { path: String, reason: String },
+}
+
+impl fmt::Display for ConfigError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			ConfigError::MissingFile { path, reason } => {
+				write!(f, "could not read {}: {}", path, reason)
+			}
+			ConfigError::Invalid { path, reason } => {
+				write!(f, "invalid {}: {}", path, reason)
+			}
+		}
+	}
+}
+
+impl std::error::Error for ConfigError {}
@@ -0,0 +1,337 @@
+use crate::camera::Camera;
+use crate::map::{Map, MapError};
+use crate::map_renderer::MapRenderer;
+use crate::tiles::{TileSet, Touch};
+use crate::world::{map_file, World};
+
+use macroquad::prelude::*;
+
+use std::rc::Rc;
+
+// What the game should do after an editor frame.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EditorAction {
+	// play the edited map with the player starting at (x, y) in map pixels.
+	PlayTest(f32, f32),
+}
+
+/*
+ * Paints the tiles of a map with the mouse and edits its portal mappings. Entities aren't spawned
+ * from the map, so their tiles stay on it and saving writes the file the way `Map::parse_map`
+ * reads it.
+ *
+ *   left mouse      paint the selected tile     right mouse   erase
+ *   middle mouse    select the tile under the cursor
+ *   mouse wheel     select a tile, or click it in the palette
+ *   arrows, WASD    scroll
+ *   M               type the target of the selected portal, an empty target removes the mapping
+ *   P               play-test from the cursor
+ *   Tab             play-test from where the editor was opened
+ *   Ctrl+S          save to maps/
+ * */
+pub struct Editor {
+	pub map_name: String,
+	pub map: Map,
+	tile_set: Rc<TileSet>,
+	// the tile painted with the left mouse button.
+	pub selected: char,
+	// the portal whose target is being typed and the text so far.
+	editing: Option<(char, String)>,
+	// where play-testing with Tab starts.
+	start: (f32, f32),
+	// changed since it was last saved.
+	pub modified: bool,
+	// outcome of the last save or play-test, shown in the HUD.
+	pub status: String,
+}
+
+impl Editor {
+
+	// pixels scrolled per second.
+	pub const SCROLL_SPEED: f32 = 20.0 * Map::TILE_SIZE;
+
+	// height of the palette along the bottom of the screen.
+	const PALETTE_HEIGHT: f32 = Map::TILE_SIZE + 16.0;
+	const PALETTE_SPACING: f32 = Map::TILE_SIZE + 8.0;
+
+	// edit the file of a map, `start` is where play-testing with Tab puts the player.
+	pub fn open(map_name: &str, tile_set: Rc<TileSet>, start: (f32, f32)) -> Result<Self, MapError> {
+		let map = Map::from_file(&map_file(map_name), tile_set.clone())?;
+		Ok(Self::new(map_name, map, tile_set, start))
+	}
+
+	pub fn new(map_name: &str, map: Map, tile_set: Rc<TileSet>, start: (f32, f32)) -> Self {
+		let selected = tile_set.chars().iter().copied().find(|&tile| tile_set.solids().contains(&tile)).unwrap_or(' ');
+		Editor {
+			map_name: map_name.to_string(),
+			map,
+			tile_set,
+			selected,
+			editing: None,
+			start,
+			modified: false,
+			status: String::new(),
+		}
+	}
+
+	// read the map again after its file changed, unless there are unsaved changes to keep.
+	pub fn reload(&mut self) -> Result<(), MapError> {
+		if self.modified {
+			self.status = format!("{} changed on disk, saving overwrites it", map_file(&self.map_name));
+			return Ok(());
+		}
+		self.map = Map::from_file(&map_file(&self.map_name), self.tile_set.clone())?;
+		Ok(())
+	}
+
+	// where play-testing with Tab puts the player.
+	pub fn set_start(&mut self, start: (f32, f32)) {
+		self.start = start;
+	}
+
+	pub fn paint(&mut self, tile_x: usize, tile_y: usize, tile: char) {
+		if tile_x < self.map.width && tile_y < self.map.height && self.map.tiles[tile_y][tile_x] != tile {
+			self.map.set_tile(tile_x, tile_y, tile);
+			self.modified = true;
+		}
+	}
+
+	// point a portal at another map, an empty target removes the mapping.
+	pub fn set_mapping(&mut self, portal: char, target: &str) {
+		let target = target.trim();
+		let target = if target.is_empty() { None } else { Some(target.to_string()) };
+		if self.map.get_mapping(portal) != target.as_ref() {
+			self.map.set_mapping(portal, target);
+			self.modified = true;
+		}
+	}
+
+	// the map as it would be saved, if it loads again.
+	pub fn check(&self) -> Result<String, MapError> {
+		let text = self.map.to_string();
+		Map::parse_map(&text, self.tile_set.clone())?;
+		Ok(text)
+	}
+
+	pub fn save(&mut self) -> Result<(), String> {
+		let text = self.check().map_err(|err| err.to_string())?;
+		let path = map_file(&self.map_name);
+		std::fs::write(&path, text).map_err(|err| format!("could not write {}: {}", path, err))?;
+		self.modified = false;
+		Ok(())
+	}
+
+	// a world on the edited map, saved or not, with the player spawning at (x, y).
+	pub fn play_test(&self, x: f32, y: f32) -> Result<World, MapError> {
+		let mut world = World::from_source(&self.map_name, &self.check()?, self.tile_set.clone())?;
+		world.set_spawn((x, y));
+		Ok(world)
+	}
+
+	// read the mouse and keyboard for one frame.
+	pub fn update(&mut self, camera: &mut Camera) -> Option<EditorAction> {
+		if self.editing.is_some() {
+			self.update_mapping();
+			return None;
+		}
+
+		// scroll
+		let ctrl = is_key_down(KeyCode::LeftControl) || is_key_down(KeyCode::RightControl);
+		let step = Self::SCROLL_SPEED * get_frame_time();
+		let mut scroll = (0.0, 0.0);
+		for (keys, direction) in [
+			([KeyCode::Left, KeyCode::A], (-1.0, 0.0)),
+			([KeyCode::Right, KeyCode::D], (1.0, 0.0)),
+			([KeyCode::Up, KeyCode::W], (0.0, -1.0)),
+			([KeyCode::Down, KeyCode::S], (0.0, 1.0)),
+		] {
+			if !ctrl && keys.iter().any(|&key| is_key_down(key)) {
+				scroll.0 += direction.0 * step;
+				scroll.1 += direction.1 * step;
+			}
+		}
+		camera.pan(scroll.0, scroll.1, &self.map);
+
+		// pick from the palette
+		let palette = self.tile_set.chars().to_vec();
+		let wheel = mouse_wheel().1;
+		if wheel != 0.0 {
+			let index = palette.iter().position(|&tile| tile == self.selected).unwrap_or(0);
+			let index = if wheel > 0.0 { index + palette.len() - 1 } else { index + 1 };
+			self.selected = palette[index % palette.len()];
+		}
+
+		let (mouse_x, mouse_y) = mouse_position();
+		let cursor = self.cursor(camera);
+		if mouse_y >= screen_height() - Self::PALETTE_HEIGHT {
+			if is_mouse_button_pressed(MouseButton::Left) {
+				let index = ((mouse_x - 8.0) / Self::PALETTE_SPACING).floor();
+				if index >= 0.0 && (index as usize) < palette.len() {
+					self.selected = palette[index as usize];
+				}
+			}
+		} else if let Some((tile_x, tile_y)) = cursor {
+			if is_mouse_button_down(MouseButton::Left) {
+				self.paint(tile_x, tile_y, self.selected);
+			} else if is_mouse_button_down(MouseButton::Right) {
+				self.paint(tile_x, tile_y, self.tile_set.blank());
+			} else if is_mouse_button_pressed(MouseButton::Middle) {
+				self.selected = self.map.tiles[tile_y][tile_x];
+			}
+		}
+
+		if ctrl && is_key_pressed(KeyCode::S) {
+			self.status = match self.save() {
+				Ok(()) => format!("saved {}", map_file(&self.map_name)),
+				Err(err) => format!("not saved: {}", err),
+			};
+		}
+		if is_key_pressed(KeyCode::M) {
+			if self.tile_set.touch(self.selected) == Some(Touch::Portal) {
+				// drop the 'm' that was just typed.
+				while get_char_pressed().is_some() {}
+				let target = self.map.get_mapping(self.selected).cloned().unwrap_or_default();
+				self.editing = Some((self.selected, target));
+			} else {
+				self.status = format!("'{}' is not a portal", self.selected);
+			}
+		}
+		if is_key_pressed(KeyCode::P) {
+			if let Some((tile_x, tile_y)) = cursor {
+				return Some(EditorAction::PlayTest(tile_x as f32 * Map::TILE_SIZE, tile_y as f32 * Map::TILE_SIZE));
+			}
+		}
+		if is_key_pressed(KeyCode::Tab) {
+			return Some(EditorAction::PlayTest(self.start.0, self.start.1));
+		}
+		None
+	}
+
+	// typing the target of a portal.
+	fn update_mapping(&mut self) {
+		let Some((portal, target)) = &mut self.editing else {
+			return;
+		};
+		while let Some(c) = get_char_pressed() {
+			if !c.is_control() {
+				target.push(c);
+			}
+		}
+		if is_key_pressed(KeyCode::Backspace) {
+			target.pop();
+		}
+		if is_key_pressed(KeyCode::Enter) || is_key_pressed(KeyCode::KpEnter) {
+			let (portal, target) = (*portal, target.clone());
+			self.editing = None;
+			self.set_mapping(portal, &target);
+		} else if is_key_pressed(KeyCode::Escape) {
+			self.editing = None;
+		}
+	}
+
+	// the map tile under the mouse.
+	fn cursor(&self, camera: &Camera) -> Option<(usize, usize)> {
+		let view = camera.view(1.0);
+		let (mouse_x, mouse_y) = mouse_position();
+		let x = ((view.x + mouse_x) / Map::TILE_SIZE).floor();
+		let y = ((view.y + mouse_y) / Map::TILE_SIZE).floor();
+		if x < 0.0 || y < 0.0 || x as usize >= self.map.width || y as usize >= self.map.height {
+			return None;
+		}
+		Some((x as usize, y as usize))
+	}
+
+	pub fn draw(&self, renderer: &MapRenderer, camera: &Camera) {
+		clear_background(DARKGRAY);
+
+		camera.activate(1.0);
+		let view = camera.view(1.0);
+		renderer.draw(&self.map, view);
+		draw_rectangle_lines(0.0, 0.0, self.map.width as f32 * Map::TILE_SIZE, self.map.height as f32 * Map::TILE_SIZE, 2.0, BLACK);
+		draw_rectangle_lines(self.start.0, self.start.1, Map::TILE_SIZE, Map::TILE_SIZE, 2.0, GREEN);
+		if let Some((tile_x, tile_y)) = self.cursor(camera) {
+			draw_rectangle_lines(tile_x as f32 * Map::TILE_SIZE, tile_y as f32 * Map::TILE_SIZE, Map::TILE_SIZE, Map::TILE_SIZE, 2.0, YELLOW);
+		}
+
+		// The HUD stays in screen coordinates.
+		set_default_camera();
+		let title = format!("Editing {}{}, tile '{}'", self.map_name, if self.modified { " (modified)" } else { "" }, self.selected);
+		draw_text(title.as_str(), 20.0, 20.0, 24.0, WHITE);
+		draw_text("Mouse: left paint, right erase, middle pick, wheel select. M portal target, P play from cursor, Tab play, Ctrl+S save", 20.0, 44.0, 20.0, WHITE);
+
+		let mut y = 68.0;
+		for (portal, target) in self.map.mappings() {
+			draw_text(format!("[{}] {}", portal, target).as_str(), 20.0, y, 20.0, WHITE);
+			y += 20.0;
+		}
+		if let Some((portal, target)) = &self.editing {
+			draw_text(format!("[{}] {}_   Enter to confirm, Esc to cancel", portal, target).as_str(), 20.0, y, 20.0, YELLOW);
+			y += 20.0;
+		}
+		if !self.status.is_empty() {
+			draw_text(self.status.as_str(), 20.0, y, 20.0, YELLOW);
+		}
+
+		// palette
+		let top = screen_height() - Self::PALETTE_HEIGHT;
+		draw_rectangle(0.0, top, screen_width(), Self::PALETTE_HEIGHT, Color::new(0.0, 0.0, 0.0, 0.6));
+		for (index, &tile) in self.tile_set.chars().iter().enumerate() {
+			let x = 8.0 + index as f32 * Self::PALETTE_SPACING;
+			renderer.draw_tile(&self.tile_set, tile, x, top + 8.0);
+			if tile == self.selected {
+				draw_rectangle_lines(x - 2.0, top + 6.0, Map::TILE_SIZE + 4.0, Map::TILE_SIZE + 4.0, 3.0, YELLOW);
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::input::Input;
+	use crate::world::Event;
+
+	fn editor(content: &str) -> Editor {
+		let tile_set = Rc::new(TileSet::from_file(TileSet::PATH).unwrap());
+		let map = Map::parse_map(content, tile_set.clone()).unwrap();
+		Editor::new("test.txt", map, tile_set, (Map::TILE_SIZE, Map::TILE_SIZE))
+	}
+
+	#[test]
+	fn test_paint_and_map_portal() {
+		let mut editor = editor("xxxx\nx  x\nxxxx\n");
+		assert!(!editor.modified);
+
+		// a portal without a target can't be saved or played.
+		editor.paint(2, 1, 'p');
+		assert!(editor.modified);
+		assert!(matches!(editor.check(), Err(MapError::PortalWithoutMapping { portal: 'p', line: 2, column: 3 })));
+		assert!(editor.play_test(Map::TILE_SIZE, Map::TILE_SIZE).is_err());
+
+		editor.set_mapping('p', " map2.txt ");
+		assert_eq!(editor.check(), Ok(String::from("xxxx\nx px\nxxxx\n\n[p] map2.txt\n")));
+
+		editor.set_mapping('p', "");
+		assert_eq!(editor.map.get_mapping('p'), None);
+
+		// painting outside the map does nothing.
+		editor.paint(4, 0, 'c');
+		assert_eq!(editor.map.width, 4);
+	}
+
+	#[test]
+	fn test_play_test_keeps_unsaved_changes() {
+		let mut editor = editor("xxxxxx\nx    x\nx    x\nxxxxxx\n");
+		editor.paint(4, 2, 'f');
+		let mut world = editor.play_test(4.0 * Map::TILE_SIZE, Map::TILE_SIZE).unwrap();
+
+		// the fire is an entity of the play-tested world, falling on it restarts at the play-test start.
+		let mut died = false;
+		for _ in 0..60 {
+			died |= world.tick(Input::default(), Vec::new()).iter().any(|event| matches!(event, Event::Died { .. }));
+		}
+		assert!(died);
+		assert_eq!(world.map.width, 6);
+		assert_eq!(world.map.tiles[2][4], ' ');
+	}
+}
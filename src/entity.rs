@@ -1 +1,620 @@
+use crate::command::Command;
+use crate::map::{Aabb, Map};
+use crate::player::Player;
+use crate::tiles::{EnemyKind, TileDef, Touch};
 
+use macroquad::prelude::*;
+
+// Index of an entity in `Entities`, stays valid when other entities are removed.
+pub type EntityId = usize;
+
+// The map character and tile (x, y) an entity was spawned from, the same wherever it moved.
+pub type Origin = (char, usize, usize);
+
+// Something on the map that isn't a plain tile: it can move, keep state and react to the player.
+pub trait Entity {
+	// the map character the entity was spawned from.
+	fn tile(&self) -> char;
+
+	// top left corner in pixels.
+	fn position(&self) -> (f32, f32);
+
+	fn bounds(&self) -> Aabb;
+
+	// advance one tick, called for all entities before touches are checked.
+	fn update(&mut self, _map: &Map, _player: &Player) -> Vec<Command> {
+		Vec::new()
+	}
+
+	// `alpha` is how far we are between the previous and the current tick.
+	fn draw(&self, sprite: Texture2D, alpha: f32);
+
+	// called every tick the player overlaps the entity.
+	fn on_touch(&mut self, id: EntityId, player: &mut Player, map: &Map) -> Vec<Command>;
+}
+
+// Removed when touched, coins and gravity switches.
+pub struct Pickup {
+	def: TileDef,
+	x: f32,
+	y: f32,
+}
+
+impl Entity for Pickup {
+
+	fn tile(&self) -> char {
+		self.def.char
+	}
+
+	fn position(&self) -> (f32, f32) {
+		(self.x, self.y)
+	}
+
+	fn bounds(&self) -> Aabb {
+		tile_bounds(self.x, self.y)
+	}
+
+	fn draw(&self, sprite: Texture2D, _alpha: f32) {
+		draw_tile(&self.def, sprite, self.x, self.y, WHITE);
+	}
+
+	fn on_touch(&mut self, id: EntityId, player: &mut Player, _map: &Map) -> Vec<Command> {
+		match self.def.touch {
+			Some(Touch::Collect) => player.add_coins(1),
+			Some(Touch::Gravity) => player.gravity_toggle(),
+			_ => {}
+		}
+		vec![Command::RemoveEntity(id)]
+	}
+}
+
+// Restarts the map when touched, e.g. fire.
+pub struct Hazard {
+	def: TileDef,
+	x: f32,
+	y: f32,
+}
+
+impl Entity for Hazard {
+
+	fn tile(&self) -> char {
+		self.def.char
+	}
+
+	fn position(&self) -> (f32, f32) {
+		(self.x, self.y)
+	}
+
+	fn bounds(&self) -> Aabb {
+		tile_bounds(self.x, self.y)
+	}
+
+	fn draw(&self, sprite: Texture2D, _alpha: f32) {
+		draw_tile(&self.def, sprite, self.x, self.y, WHITE);
+	}
+
+	fn on_touch(&mut self, _id: EntityId, _player: &mut Player, _map: &Map) -> Vec<Command> {
+		let (tile_x, tile_y) = tile_of(self.bounds());
+		vec![Command::Die(self.def.char, tile_x, tile_y)]
+	}
+}
+
+// Moves the spawn here when touched, dying later goes back to how the map was at that moment.
+pub struct Checkpoint {
+	def: TileDef,
+	x: f32,
+	y: f32,
+	// the player respawns here, drawn lit.
+	active: bool,
+}
+
+impl Entity for Checkpoint {
+
+	fn tile(&self) -> char {
+		self.def.char
+	}
+
+	fn position(&self) -> (f32, f32) {
+		(self.x, self.y)
+	}
+
+	fn bounds(&self) -> Aabb {
+		tile_bounds(self.x, self.y)
+	}
+
+	fn update(&mut self, _map: &Map, player: &Player) -> Vec<Command> {
+		self.active = (player.spawn_x, player.spawn_y) == (self.x, self.y);
+		Vec::new()
+	}
+
+	fn draw(&self, sprite: Texture2D, _alpha: f32) {
+		draw_tile(&self.def, sprite, self.x, self.y, if self.active { WHITE } else { GRAY });
+	}
+
+	fn on_touch(&mut self, id: EntityId, _player: &mut Player, _map: &Map) -> Vec<Command> {
+		if self.active {
+			return Vec::new();
+		}
+		self.active = true;
+		vec![Command::Checkpoint(id)]
+	}
+}
+
+// Loads the map its character is mapped to. A portal only works once the player has been
+// outside of it, so arriving on the exit portal of the next map doesn't send us straight back.
+pub struct Portal {
+	def: TileDef,
+	x: f32,
+	y: f32,
+	armed: bool,
+}
+
+impl Entity for Portal {
+
+	fn tile(&self) -> char {
+		self.def.char
+	}
+
+	fn position(&self) -> (f32, f32) {
+		(self.x, self.y)
+	}
+
+	fn bounds(&self) -> Aabb {
+		tile_bounds(self.x, self.y)
+	}
+
+	fn update(&mut self, _map: &Map, player: &Player) -> Vec<Command> {
+		if !self.bounds().overlaps(&player.touch_bounds()) {
+			self.armed = true;
+		}
+		Vec::new()
+	}
+
+	fn draw(&self, sprite: Texture2D, _alpha: f32) {
+		draw_tile(&self.def, sprite, self.x, self.y, WHITE);
+	}
+
+	fn on_touch(&mut self, _id: EntityId, _player: &mut Player, map: &Map) -> Vec<Command> {
+		if !self.armed {
+			return Vec::new();
+		}
+		self.armed = false;
+		match map.get_mapping(self.def.char) {
+			Some(next_map) => vec![Command::LoadMap(next_map.to_string(), self.def.char)],
+			None => Vec::new(),
+		}
+	}
+}
+
+// Moves on its own and kills the player on contact, stompable enemies die when landed on instead.
+pub struct Enemy {
+	def: TileDef,
+	kind: EnemyKind,
+	x: f32,
+	y: f32,
+	// position at the start of the last tick, used to interpolate drawing between ticks.
+	prev_x: f32,
+	prev_y: f32,
+	// 1.0 when patrolling to the right, -1.0 to the left.
+	direction: f32,
+}
+
+impl Enemy {
+
+	// speeds in pixels per tick.
+	pub const WALK_SPEED: f32 = 1.5;
+	pub const FLY_SPEED: f32 = 2.0;
+	pub const CHASE_SPEED: f32 = 1.5;
+	pub const FALL_SPEED: f32 = 8.0;
+
+	// chasers only go after a player at most this many steps away.
+	pub const CHASE_RANGE: usize = 12;
+
+	fn new(def: TileDef, kind: EnemyKind, x: f32, y: f32) -> Self {
+		Enemy {
+			def,
+			kind,
+			x,
+			y,
+			prev_x: x,
+			prev_y: y,
+			direction: 1.0,
+		}
+	}
+
+	// move sideways, turning around against walls and, if we need a floor, in front of ledges.
+	fn patrol(&mut self, map: &Map, speed: f32, needs_floor: bool) {
+		let bounds = self.bounds();
+		let center = (bounds.x + bounds.w / 2.0, bounds.y + bounds.h / 2.0);
+		let wall = map.raycast(center, (self.direction, 0.0), bounds.w / 2.0 + Map::SKIN).is_some();
+		let ledge = needs_floor && {
+			let edge = center.0 + self.direction * (bounds.w / 2.0 + speed);
+			map.raycast((edge, bounds.y + bounds.h - 1.0), (0.0, 1.0), 2.0).is_none()
+		};
+		if wall || ledge {
+			self.direction = -self.direction;
+		}
+
+		let sweep = map.sweep(bounds, (self.direction * speed, 0.0), map.solids());
+		self.x += sweep.motion.0;
+	}
+
+	// float towards the next tile on the way to the player.
+	fn chase(&mut self, map: &Map, player: &Player) {
+		let Some(path) = map.find_path(tile_of(self.bounds()), tile_of(player.bounds()), Self::CHASE_RANGE) else {
+			return;
+		};
+		let (target_x, target_y) = match path.first() {
+			Some(&(tile_x, tile_y)) => (tile_x as f32 * Map::TILE_SIZE, tile_y as f32 * Map::TILE_SIZE),
+			None => (player.x, player.y),
+		};
+
+		let step_x = (target_x - self.x).clamp(-Self::CHASE_SPEED, Self::CHASE_SPEED);
+		let step_y = (target_y - self.y).clamp(-Self::CHASE_SPEED, Self::CHASE_SPEED);
+		let sweep = map.sweep(self.bounds(), (step_x, step_y), map.solids());
+		self.x += sweep.motion.0;
+		self.y += sweep.motion.1;
+	}
+
+}
+
+impl Entity for Enemy {
+
+	fn tile(&self) -> char {
+		self.def.char
+	}
+
+	fn position(&self) -> (f32, f32) {
+		(self.x, self.y)
+	}
+
+	fn bounds(&self) -> Aabb {
+		tile_bounds(self.x, self.y)
+	}
+
+	fn update(&mut self, map: &Map, player: &Player) -> Vec<Command> {
+		self.prev_x = self.x;
+		self.prev_y = self.y;
+
+		match self.kind {
+			EnemyKind::Walker => {
+				let fall = map.sweep(self.bounds(), (0.0, Self::FALL_SPEED), map.solids());
+				self.y += fall.motion.1;
+				if fall.normal.1 < 0.0 {
+					self.patrol(map, Self::WALK_SPEED, true);
+				}
+			}
+			EnemyKind::Flyer => self.patrol(map, Self::FLY_SPEED, false),
+			EnemyKind::Chaser => self.chase(map, player),
+		}
+		Vec::new()
+	}
+
+	fn draw(&self, sprite: Texture2D, alpha: f32) {
+		let x = self.prev_x + (self.x - self.prev_x) * alpha;
+		let y = self.prev_y + (self.y - self.prev_y) * alpha;
+		draw_tile(&self.def, sprite, x, y, WHITE);
+	}
+
+	fn on_touch(&mut self, id: EntityId, player: &mut Player, _map: &Map) -> Vec<Command> {
+		// stomped if the player was above us last tick and is coming down.
+		let from_above = player.prev_y + Player::HEIGHT <= self.prev_y + 1.0 && player.y > player.prev_y;
+		if self.def.stompable && from_above {
+			player.bounce();
+			vec![Command::RemoveEntity(id)]
+		} else {
+			let (tile_x, tile_y) = tile_of(self.bounds());
+			vec![Command::Die(self.def.char, tile_x, tile_y)]
+		}
+	}
+}
+
+// All entities of the current map.
+pub struct Entities {
+	// indexed by `EntityId`, removed entities leave a hole so ids stay valid.
+	slots: Vec<Option<Box<dyn Entity>>>,
+	// where every slot was spawned from, also indexed by `EntityId`.
+	origins: Vec<Origin>,
+}
+
+impl Entities {
+
+	// Turns the map characters of enemies and tiles with a touch behaviour into entities and leaves blank tiles behind.
+	pub fn spawn(map: &mut Map) -> Self {
+		let mut spawned: Vec<(usize, usize, Box<dyn Entity>)> = Vec::new();
+		for (row_index, row) in map.tiles.iter().enumerate() {
+			for (col_index, &tile) in row.iter().enumerate() {
+				let Some(def) = map.tile_set().get(tile) else {
+					continue;
+				};
+				let x = col_index as f32 * Map::TILE_SIZE;
+				let y = row_index as f32 * Map::TILE_SIZE;
+				if let Some(entity) = Self::from_tile(def, x, y) {
+					spawned.push((col_index, row_index, entity));
+				}
+			}
+		}
+
+		let blank = map.tile_set().blank();
+		let mut entities = Entities { slots: Vec::new(), origins: Vec::new() };
+		for (tile_x, tile_y, entity) in spawned {
+			map.set_tile(tile_x, tile_y, blank);
+			entities.origins.push((entity.tile(), tile_x, tile_y));
+			entities.slots.push(Some(entity));
+		}
+		entities
+	}
+
+	fn from_tile(def: &TileDef, x: f32, y: f32) -> Option<Box<dyn Entity>> {
+		let def = def.clone();
+		if let Some(kind) = def.enemy {
+			return Some(Box::new(Enemy::new(def, kind, x, y)));
+		}
+		match def.touch? {
+			Touch::Collect | Touch::Gravity => Some(Box::new(Pickup { def, x, y })),
+			Touch::Kill => Some(Box::new(Hazard { def, x, y })),
+			Touch::Portal => Some(Box::new(Portal { def, x, y, armed: false })),
+			Touch::Checkpoint => Some(Box::new(Checkpoint { def, x, y, active: false })),
+		}
+	}
+
+	/*
+	 * Update all entities, then let every one the player overlaps react. Gravity pickups touched
+	 * together toggle gravity once and are all used up, toggling for each would cancel out.
+	 * */
+	pub fn update(&mut self, map: &Map, player: &mut Player) -> Vec<Command> {
+		let mut commands = Vec::new();
+		for entity in self.slots.iter_mut().flatten() {
+			commands.extend(entity.update(map, player));
+		}
+
+		let touch = player.touch_bounds();
+		let mut toggled = false;
+		for (id, slot) in self.slots.iter_mut().enumerate() {
+			if let Some(entity) = slot {
+				if entity.bounds().overlaps(&touch) {
+					let gravity = map.tile_set().touch(entity.tile()) == Some(Touch::Gravity);
+					if gravity && toggled {
+						commands.push(Command::RemoveEntity(id));
+					} else {
+						commands.extend(entity.on_touch(id, player, map));
+					}
+					toggled |= gravity;
+				}
+			}
+		}
+		commands
+	}
+
+	// draw the entities inside `view`.
+	pub fn draw(&self, sprite: Texture2D, alpha: f32, view: Rect) {
+		for entity in self.slots.iter().flatten() {
+			let bounds = entity.bounds();
+			if view.overlaps(&Rect::new(bounds.x, bounds.y, bounds.w, bounds.h)) {
+				entity.draw(sprite, alpha);
+			}
+		}
+	}
+
+	pub fn get(&self, id: EntityId) -> Option<&dyn Entity> {
+		self.slots.get(id).and_then(|slot| slot.as_deref())
+	}
+
+	pub fn remove(&mut self, id: EntityId) {
+		if let Some(slot) = self.slots.get_mut(id) {
+			*slot = None;
+		}
+	}
+
+	// where the entities removed since they were spawned came from.
+	pub fn removed(&self) -> Vec<Origin> {
+		self.slots.iter()
+			.zip(&self.origins)
+			.filter(|(slot, _)| slot.is_none())
+			.map(|(_, &origin)| origin)
+			.collect()
+	}
+
+	// remove the entity spawned from `origin`, if there is one.
+	pub fn remove_spawned(&mut self, origin: Origin) {
+		if let Some(id) = self.origins.iter().position(|&spawned| spawned == origin) {
+			self.remove(id);
+		}
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item = (EntityId, &dyn Entity)> {
+		self.slots.iter()
+			.enumerate()
+			.filter_map(|(id, slot)| slot.as_deref().map(|entity| (id, entity)))
+	}
+
+	// position of the first entity spawned from `tile`, e.g. the exit portal of a map.
+	pub fn find(&self, tile: char) -> Option<(f32, f32)> {
+		self.slots.iter()
+			.flatten()
+			.find(|entity| entity.tile() == tile)
+			.map(|entity| entity.position())
+	}
+}
+
+// the tile under the middle of a box.
+fn tile_of(bounds: Aabb) -> (usize, usize) {
+	let x = (bounds.x + bounds.w / 2.0).max(0.0) / Map::TILE_SIZE;
+	let y = (bounds.y + bounds.h / 2.0).max(0.0) / Map::TILE_SIZE;
+	(x as usize, y as usize)
+}
+
+fn tile_bounds(x: f32, y: f32) -> Aabb {
+	Aabb { x, y, w: Map::TILE_SIZE, h: Map::TILE_SIZE }
+}
+
+// draw an entity that looks like its tile definition tinted with `tint`, animated tiles at the current time.
+fn draw_tile(def: &TileDef, sprite: Texture2D, x: f32, y: f32, tint: Color) {
+	match (def.cell(get_time() as f32), def.color) {
+		(Some((pos_x, pos_y)), _) => {
+			draw_texture_ex(
+				sprite,
+				x,
+				y,
+				tint,
+				DrawTextureParams {
+					dest_size: Some(vec2(Map::TILE_SIZE, Map::TILE_SIZE)),
+					source: Some(Rect::new(pos_x as f32 * Map::TILE_SIZE, pos_y as f32 * Map::TILE_SIZE, Map::TILE_SIZE, Map::TILE_SIZE)),
+					rotation: def.rotation.to_radians(),
+					pivot: None,
+					..Default::default()
+				},
+			);
+		}
+		(None, Some((r, g, b))) => {
+			let color = Color::from_rgba(r, g, b, 255);
+			draw_rectangle(x, y, Map::TILE_SIZE, Map::TILE_SIZE, Color::new(color.r * tint.r, color.g * tint.g, color.b * tint.b, tint.a));
+		}
+		(None, None) => {
+			draw_rectangle(x, y, Map::TILE_SIZE, Map::TILE_SIZE, PINK);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::tiles::TileSet;
+
+	use std::rc::Rc;
+
+	fn parse(content: &str) -> Map {
+		let tile_set = TileSet::from_file(TileSet::PATH).unwrap();
+		Map::parse_map(content, Rc::new(tile_set)).unwrap()
+	}
+
+	fn player_at(x: f32, y: f32) -> Player {
+		let mut player = Player::new();
+		player.x = x;
+		player.y = y;
+		player
+	}
+
+	#[test]
+	fn test_spawn_leaves_blank_tiles() {
+		let mut map = parse("xxxxxx\nxcgfpx\nxxxxxx\n\n[p] map2.txt\n");
+		let entities = Entities::spawn(&mut map);
+		assert_eq!(entities.slots.len(), 4);
+		assert_eq!(map.tiles[1], vec!['x', ' ', ' ', ' ', ' ', 'x']);
+		assert_eq!(entities.find('p'), Some((128.0, 32.0)));
+		assert_eq!(entities.find('q'), None);
+	}
+
+	#[test]
+	fn test_collect_coin() {
+		let mut map = parse("xxxxx\nx c x\nxxxxx\n");
+		let mut entities = Entities::spawn(&mut map);
+		let mut player = player_at(64.0, 32.0);
+
+		let commands = entities.update(&map, &mut player);
+		assert!(matches!(commands.as_slice(), [Command::RemoveEntity(0)]));
+		assert_eq!(player.coins(), 1);
+
+		entities.remove(0);
+		assert!(entities.update(&map, &mut player).is_empty());
+		assert_eq!(player.coins(), 1);
+	}
+
+	#[test]
+	fn test_gravity_pickups_touched_together_toggle_once() {
+		let mut map = parse("xxxxx\nx ggx\nxxxxx\n");
+		let mut entities = Entities::spawn(&mut map);
+		let mut player = player_at(80.0, 32.0);
+
+		let commands = entities.update(&map, &mut player);
+		assert!(matches!(commands.as_slice(), [Command::RemoveEntity(0), Command::RemoveEntity(1)]));
+		assert!(!player.has_gravity());
+	}
+
+	#[test]
+	fn test_standing_next_to_fire() {
+		let mut map = parse("xxxxx\nx f x\nxxxxx\n");
+		let mut entities = Entities::spawn(&mut map);
+
+		let mut player = player_at(32.0, 32.0);
+		assert!(entities.update(&map, &mut player).is_empty());
+
+		let mut player = player_at(40.0, 32.0);
+		assert!(matches!(entities.update(&map, &mut player).as_slice(), [Command::Die(..)]));
+	}
+
+	#[test]
+	fn test_portal_arms_after_leaving() {
+		let mut map = parse("xxxxx\nxp  x\nxxxxx\n\n[p] map2.txt\n");
+		let mut entities = Entities::spawn(&mut map);
+
+		// arriving on the portal doesn't use it.
+		let mut player = player_at(32.0, 32.0);
+		assert!(entities.update(&map, &mut player).is_empty());
+
+		player.x = 64.0;
+		assert!(entities.update(&map, &mut player).is_empty());
+
+		player.x = 48.0;
+		let commands = entities.update(&map, &mut player);
+		assert!(matches!(commands.as_slice(), [Command::LoadMap(name, 'p')] if name == "map2.txt"));
+	}
+
+	#[test]
+	fn test_walker_patrols_between_wall_and_ledge() {
+		let mut map = parse("xxxxxxxx\nx  w   x\nxxxxx  x\nx      x\nxxxxxxxx\n");
+		let mut entities = Entities::spawn(&mut map);
+		let mut player = player_at(32.0, 96.0);
+
+		let mut xs = Vec::new();
+		for _ in 0..300 {
+			entities.update(&map, &mut player);
+			let (x, y) = entities.find('w').unwrap();
+			assert_eq!(y, 32.0);
+			xs.push(x);
+		}
+		// turns at the wall on the left and before walking off the floor on the right.
+		let min = xs.iter().copied().fold(f32::INFINITY, f32::min);
+		let max = xs.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+		assert_eq!(min, 32.0);
+		assert!(max > 120.0 && max <= 128.0, "{}", max);
+	}
+
+	#[test]
+	fn test_stomp_or_die() {
+		let mut map = parse("xxxxx\nx   x\nx w x\nxxxxx\n");
+		let mut entities = Entities::spawn(&mut map);
+
+		// landing from above kills the walker and bounces.
+		let mut player = player_at(64.0, 40.0);
+		player.prev_y = 30.0;
+		let commands = entities.update(&map, &mut player);
+		assert!(matches!(commands.as_slice(), [Command::RemoveEntity(0)]));
+		assert!(player.vy() < 0.0);
+
+		// walking into it from the side doesn't.
+		let mut player = player_at(40.0, 64.0);
+		player.prev_x = 30.0;
+		player.prev_y = 64.0;
+		assert!(matches!(entities.update(&map, &mut player).as_slice(), [Command::Die(..)]));
+	}
+
+	#[test]
+	fn test_chaser_follows_player() {
+		let mut map = parse("xxxxxxxxxx\nx       hx\nx xxxxxx x\nx        x\nxxxxxxxxxx\n");
+		let mut entities = Entities::spawn(&mut map);
+
+		// the player is around the wall in the bottom left corner.
+		let mut player = player_at(32.0, 96.0);
+		let mut caught = false;
+		for _ in 0..400 {
+			if matches!(entities.update(&map, &mut player).as_slice(), [Command::Die(..)]) {
+				caught = true;
+				break;
+			}
+		}
+		assert!(caught);
+		let (x, y) = entities.find('h').unwrap();
+		assert!(x < 64.0 && y > 64.0, "{} {}", x, y);
+	}
+}
@@ -0,0 +1,110 @@
+use gilrs::{Axis, Button, EventType, Gilrs};
+
+use std::collections::HashMap;
+
+// The buttons of all connected gamepads, read through gilrs as macroquad has no gamepad support.
+// Without a gamepad backend nothing is ever pressed.
+pub struct Gamepads {
+	gilrs: Option<Gilrs>,
+	// buttons pressed since the last update.
+	pressed: Vec<Button>,
+	// sticks pushed past the threshold since the last update, with the direction, -1 or 1.
+	pushed: Vec<(Axis, i8)>,
+	// which way every stick is pushed, -1, 0 or 1.
+	sticks: HashMap<Axis, i8>,
+}
+
+impl Gamepads {
+
+	// how far a stick has to be pushed to count as a press.
+	pub const STICK_THRESHOLD: f32 = 0.5;
+
+	pub fn new() -> Self {
+		let gilrs = match Gilrs::new() {
+			Ok(gilrs) => Some(gilrs),
+			Err(err) => {
+				eprintln!("No gamepad support: {}", err);
+				None
+			}
+		};
+		Gamepads {
+			gilrs,
+			pressed: Vec::new(),
+			pushed: Vec::new(),
+			sticks: HashMap::new(),
+		}
+	}
+
+	// read what happened since the last frame, call once per frame.
+	pub fn update(&mut self) {
+		self.pressed.clear();
+		self.pushed.clear();
+		let Some(gilrs) = &mut self.gilrs else {
+			return;
+		};
+		while let Some(event) = gilrs.next_event() {
+			match event.event {
+				EventType::ButtonPressed(button, _) => self.pressed.push(button),
+				EventType::AxisChanged(axis, value, _) => {
+					let direction = if value > Self::STICK_THRESHOLD {
+						1
+					} else if value < -Self::STICK_THRESHOLD {
+						-1
+					} else {
+						0
+					};
+					if direction != 0 && self.sticks.get(&axis) != Some(&direction) {
+						self.pushed.push((axis, direction));
+						// pushing the left stick up or down presses the d-pad, so it moves through menus too.
+						if axis == Axis::LeftStickY {
+							self.pressed.push(if direction > 0 { Button::DPadUp } else { Button::DPadDown });
+						}
+					}
+					self.sticks.insert(axis, direction);
+				}
+				_ => {}
+			}
+		}
+	}
+
+	// true if any gamepad pressed `button` since the last update.
+	pub fn is_pressed(&self, button: Button) -> bool {
+		self.pressed.contains(&button)
+	}
+
+	// true if any gamepad pushed `axis` past the threshold in `direction` since the last update.
+	pub fn is_pushed(&self, axis: Axis, direction: i8) -> bool {
+		self.pushed.contains(&(axis, direction))
+	}
+
+	// the first button pressed since the last update, if any.
+	pub fn first_pressed(&self) -> Option<Button> {
+		self.pressed.first().copied()
+	}
+
+	// the first stick pushed since the last update, if any.
+	pub fn first_pushed(&self) -> Option<(Axis, i8)> {
+		self.pushed.first().copied()
+	}
+
+	// true if `button` is held down on any gamepad.
+	pub fn is_down(&self, button: Button) -> bool {
+		self.gilrs.as_ref().is_some_and(|gilrs| gilrs.gamepads().any(|(_, gamepad)| gamepad.is_pressed(button)))
+	}
+
+	// the position of an axis, from the gamepad pushing it furthest.
+	pub fn axis(&self, axis: Axis) -> f32 {
+		let Some(gilrs) = &self.gilrs else {
+			return 0.0;
+		};
+		gilrs.gamepads()
+			.map(|(_, gamepad)| gamepad.value(axis))
+			.fold(0.0, |furthest: f32, value| if value.abs() > furthest.abs() { value } else { furthest })
+	}
+}
+
+impl Default for Gamepads {
+	fn default() -> Self {
+		Self::new()
+	}
+}
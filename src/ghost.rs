@@ -0,0 +1,275 @@
+use crate::config::ConfigError;
+use crate::player::{Player, Pose};
+use crate::tiles::TileSet;
+
+use macroquad::prelude::*;
+
+use std::fmt;
+use std::path::Path;
+
+/*
+ * Where the player was at the start of every tick of one pass through a map and its pose, from
+ * entering it until leaving it through a portal. The fastest pass of every map is kept on disk and
+ * drawn as a translucent player, at the same tick since entering the map as the player, to race
+ * against. Saved as text, "l" or "r" being the way the player faced:
+ *
+ *   xrunner ghost 2
+ *   map map1.txt
+ *   positions
+ *   32.00,32.00,fall,r 32.00,32.17,fall,r ...
+ *
+ * Version 1 ghosts only have the positions, they are drawn standing facing right.
+ * */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ghost {
+	pub map: String,
+	samples: Vec<Sample>,
+}
+
+// The player at the start of one tick.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Sample {
+	x: f32,
+	y: f32,
+	pose: Pose,
+	facing_left: bool,
+}
+
+impl Ghost {
+
+	pub const DIR: &'static str = "ghosts";
+
+	const HEADER: &'static str = "xrunner ghost 2";
+
+	// ghosts saved before poses were, still read.
+	const HEADER_V1: &'static str = "xrunner ghost 1";
+
+	// positions written per line.
+	const POSITIONS_PER_LINE: usize = 10;
+
+	// how see-through ghosts are drawn.
+	const COLOR: Color = Color::new(1.0, 1.0, 1.0, 0.4);
+
+	pub fn new(map: &str) -> Self {
+		Ghost {
+			map: map.to_string(),
+			samples: Vec::new(),
+		}
+	}
+
+	// where the fastest pass through `map` is kept.
+	pub fn path(map: &str) -> String {
+		format!("{}/{}.ghost", Self::DIR, map)
+	}
+
+	pub fn record(&mut self, player: &Player) {
+		self.samples.push(Sample {
+			x: player.x,
+			y: player.y,
+			pose: player.pose(),
+			facing_left: player.is_facing_left(),
+		});
+	}
+
+	// the number of ticks the pass took.
+	pub fn len(&self) -> usize {
+		self.samples.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.samples.is_empty()
+	}
+
+	// where the ghost is at the start of `tick`, None once it left the map.
+	pub fn position(&self, tick: usize) -> Option<(f32, f32)> {
+		self.samples.get(tick).map(|sample| (sample.x, sample.y))
+	}
+
+	// the pose at the start of `tick` and whether it faces left.
+	pub fn pose(&self, tick: usize) -> Option<(Pose, bool)> {
+		self.samples.get(tick).map(|sample| (sample.pose, sample.facing_left))
+	}
+
+	// true if this pass was faster than `best`, or there is none.
+	pub fn beats(&self, best: Option<&Ghost>) -> bool {
+		!self.is_empty() && best.is_none_or(|best| self.len() < best.len())
+	}
+
+	// draw the ghost `alpha` of the way from the start of tick `tick - 1` to the start of `tick`,
+	// animated like the player.
+	pub fn draw(&self, sprite: Texture2D, tile_set: &TileSet, tick: usize, alpha: f32) {
+		let Some(sample) = self.samples.get(tick) else {
+			return;
+		};
+		let prev = self.samples.get(tick.saturating_sub(1)).unwrap_or(sample);
+		// the animation runs from the tick the pose was taken.
+		let pose_ticks = self.samples[..tick].iter().rev().take_while(|earlier| earlier.pose == sample.pose).count();
+		let position = (prev.x + (sample.x - prev.x) * alpha, prev.y + (sample.y - prev.y) * alpha);
+		Player::draw_pose(sprite, tile_set, position, sample.pose, pose_ticks as f32 + alpha, sample.facing_left, Self::COLOR);
+	}
+
+	pub fn load(path: &str) -> Result<Self, ConfigError> {
+		let content = std::fs::read_to_string(path).map_err(|err| ConfigError::MissingFile {
+			path: path.to_string(),
+			reason: err.to_string(),
+		})?;
+
+		Self::parse(&content).map_err(|reason| ConfigError::Invalid {
+			path: path.to_string(),
+			reason,
+		})
+	}
+
+	pub fn save(&self, path: &str) -> std::io::Result<()> {
+		if let Some(dir) = Path::new(path).parent() {
+			std::fs::create_dir_all(dir)?;
+		}
+		std::fs::write(path, self.to_string())
+	}
+
+	pub fn parse(content: &str) -> Result<Self, String> {
+		let mut lines = content.lines().enumerate();
+
+		let with_poses = match lines.next() {
+			Some((_, line)) if line.trim() == Self::HEADER => true,
+			Some((_, line)) if line.trim() == Self::HEADER_V1 => false,
+			_ => return Err(format!("line 1: expected '{}'", Self::HEADER)),
+		};
+		let map = match lines.next() {
+			Some((_, line)) => match line.trim().split_once(' ') {
+				Some(("map", map)) => map.trim().to_string(),
+				_ => return Err(String::from("line 2: expected 'map ...'")),
+			},
+			None => return Err(String::from("missing 'map'")),
+		};
+		match lines.next() {
+			Some((_, line)) if line.trim() == "positions" => {}
+			Some((index, _)) => return Err(format!("line {}: expected 'positions'", index + 1)),
+			None => return Err(String::from("missing 'positions'")),
+		}
+
+		let mut ghost = Ghost::new(&map);
+		for (index, line) in lines {
+			for sample in line.split_whitespace() {
+				let parsed = if with_poses { Self::parse_sample(sample) } else { Self::parse_position(sample) };
+				let Some(parsed) = parsed else {
+					let expected = if with_poses { "x,y,pose,l or r" } else { "x,y" };
+					return Err(format!("line {}: malformed position '{}', expected '{}'", index + 1, sample, expected));
+				};
+				ghost.samples.push(parsed);
+			}
+		}
+		Ok(ghost)
+	}
+
+	// "x,y,pose,l" or "x,y,pose,r".
+	fn parse_sample(text: &str) -> Option<Sample> {
+		let mut fields = text.split(',');
+		let (x, y, pose, facing) = (fields.next()?, fields.next()?, fields.next()?, fields.next()?);
+		if fields.next().is_some() {
+			return None;
+		}
+		Some(Sample {
+			x: x.parse().ok()?,
+			y: y.parse().ok()?,
+			pose: *Pose::ALL.iter().find(|known| known.name() == pose)?,
+			facing_left: match facing {
+				"l" => true,
+				"r" => false,
+				_ => return None,
+			},
+		})
+	}
+
+	// "x,y" of a version 1 ghost.
+	fn parse_position(text: &str) -> Option<Sample> {
+		let (x, y) = text.split_once(',')?;
+		Some(Sample { x: x.parse().ok()?, y: y.parse().ok()?, pose: Pose::Idle, facing_left: false })
+	}
+}
+
+impl fmt::Display for Ghost {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		writeln!(f, "{}", Self::HEADER)?;
+		writeln!(f, "map {}", self.map)?;
+		writeln!(f, "positions")?;
+
+		for line in self.samples.chunks(Self::POSITIONS_PER_LINE) {
+			let samples: Vec<String> = line.iter()
+				.map(|sample| format!("{:.2},{:.2},{},{}", sample.x, sample.y, sample.pose.name(), if sample.facing_left { 'l' } else { 'r' }))
+				.collect();
+			writeln!(f, "{}", samples.join(" "))?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::map::Map;
+
+	use std::rc::Rc;
+
+	fn pass(ticks: usize) -> Ghost {
+		let mut ghost = Ghost::new("map1.txt");
+		let mut player = Player::new();
+		for tick in 0..ticks {
+			player.x = 32.0 + tick as f32 * 1.5;
+			ghost.record(&player);
+		}
+		ghost
+	}
+
+	#[test]
+	fn test_round_trip() {
+		let ghost = pass(25);
+		let text = ghost.to_string();
+		assert!(text.starts_with("xrunner ghost 2\nmap map1.txt\npositions\n32.00,32.00,idle,r 33.50,32.00,idle,r "));
+		assert_eq!(text.lines().count(), 6);
+		assert_eq!(Ghost::parse(&text), Ok(ghost));
+
+		assert!(Ghost::parse("xrunner ghost 2\nmap map1.txt\npositions\n32.00,32.00,idle\n").is_err());
+		assert!(Ghost::parse("xrunner ghost 2\nmap map1.txt\npositions\n32.00,32.00,walk,r\n").is_err());
+		assert!(Ghost::parse("xrunner ghost 1\nmap map1.txt\npositions\n32.00;32.00\n").is_err());
+		assert!(Ghost::parse("xrunner ghost 2\npositions\n").is_err());
+
+		// older ghosts without poses stand facing right.
+		let old = Ghost::parse("xrunner ghost 1\nmap map1.txt\npositions\n32.00,32.00 33.50,32.00\n").unwrap();
+		assert_eq!(old.position(1), Some((33.5, 32.0)));
+		assert_eq!(old.pose(1), Some((Pose::Idle, false)));
+	}
+
+	#[test]
+	fn test_records_pose() {
+		let map = Map::parse_map("xxxxxx\nx    x\nxxxxxx\n", Rc::new(TileSet::from_file(TileSet::PATH).unwrap())).unwrap();
+		let mut ghost = Ghost::new("map1.txt");
+		let mut player = Player::new();
+		player.update(&map);
+		ghost.record(&player);
+		player.move_right();
+		player.update(&map);
+		ghost.record(&player);
+		player.move_left();
+		player.update(&map);
+		ghost.record(&player);
+		assert_eq!(ghost.pose(0), Some((Pose::Idle, false)));
+		assert_eq!(ghost.pose(2), Some((Pose::Run, true)));
+
+		let text = ghost.to_string();
+		assert!(text.ends_with("32.00,32.00,idle,r 37.00,32.00,run,r 32.00,32.00,run,l\n"));
+		assert_eq!(Ghost::parse(&text), Ok(ghost));
+	}
+
+	#[test]
+	fn test_faster_pass_beats() {
+		let ghost = pass(25);
+		assert_eq!(ghost.position(1), Some((33.5, 32.0)));
+		assert_eq!(ghost.position(25), None);
+
+		assert!(pass(20).beats(Some(&ghost)));
+		assert!(!pass(25).beats(Some(&ghost)));
+		assert!(ghost.beats(None));
+		assert!(!Ghost::new("map1.txt").beats(None));
+	}
+}
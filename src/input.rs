@@ -0,0 +1,86 @@
+use crate::command::Command;
+use crate::map::Map;
+use crate::player::Player;
+
+// The actions held down during one tick, kept as a bitmask so runs can be recorded compactly.
+// See `Bindings::input` for the keys and gamepad buttons behind them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Input(pub u8);
+
+impl Input {
+
+	pub const JUMP: u8 = 1;
+	pub const LEFT: u8 = 2;
+	pub const RIGHT: u8 = 4;
+	pub const UP: u8 = 8;
+	pub const DOWN: u8 = 16;
+	pub const RESET: u8 = 32;
+
+	// names of the keys in scripts, see `Replay::from_script`.
+	pub const NAMES: [(&'static str, u8); 6] = [
+		("jump", Self::JUMP),
+		("left", Self::LEFT),
+		("right", Self::RIGHT),
+		("up", Self::UP),
+		("down", Self::DOWN),
+		("reset", Self::RESET),
+	];
+
+	// keys by name joined with '+', e.g. "right+jump", or "none".
+	pub fn from_names(names: &str) -> Result<Self, String> {
+		if names == "none" {
+			return Ok(Input(0));
+		}
+		let mut bits = 0;
+		for name in names.split('+') {
+			match Self::NAMES.iter().find(|(known, _)| *known == name) {
+				Some((_, bit)) => bits |= bit,
+				None => return Err(format!("unknown key '{}'", name)),
+			}
+		}
+		Ok(Input(bits))
+	}
+
+	// the opposite of `from_names`.
+	pub fn names(self) -> String {
+		let names: Vec<&str> = Self::NAMES.iter()
+			.filter(|(_, bit)| self.is_down(*bit))
+			.map(|(name, _)| *name)
+			.collect();
+		if names.is_empty() {
+			String::from("none")
+		} else {
+			names.join("+")
+		}
+	}
+
+	pub fn is_down(self, bit: u8) -> bool {
+		self.0 & bit != 0
+	}
+
+	// drive the player with the keys of one tick.
+	pub fn apply(self, player: &mut Player, map: &Map) -> Vec<Command> {
+		let mut ret: Vec<Command> = Vec::new();
+
+		if self.is_down(Self::JUMP) {
+			player.jump(map);
+		}
+		if self.is_down(Self::LEFT) {
+			player.move_left()
+		}
+		if self.is_down(Self::RIGHT) {
+			player.move_right()
+		}
+		if self.is_down(Self::UP) {
+			player.move_up(map)
+		}
+		if self.is_down(Self::DOWN) {
+			player.move_down()
+		}
+		if self.is_down(Self::RESET) {
+			ret.push(Command::ResetMap());
+		}
+
+		ret
+	}
+}
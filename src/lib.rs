@@ -0,0 +1,26 @@
+// The game simulation and drawing, shared by the game and the headless runner in src/bin.
+pub mod animation;
+pub mod audio;
+pub mod autotile;
+pub mod bindings;
+pub mod camera;
+pub mod command;
+pub mod config;
+pub mod editor;
+pub mod entity;
+pub mod gamepad;
+pub mod ghost;
+pub mod input;
+pub mod map;
+pub mod map_renderer;
+pub mod menu;
+pub mod particles;
+pub mod player;
+pub mod replay;
+pub mod save;
+pub mod solver;
+pub mod speedrun;
+pub mod ticker;
+pub mod tiles;
+pub mod watch;
+pub mod world;
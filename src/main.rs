@@ -1,19 +1,56 @@
+use gilrs::Button;
 use macroquad::prelude::*;
+
+// Bring Player and Map into scope
+use xrunner::audio::{Audio, Effect};
+use xrunner::bindings::{Action, Binding, Bindings};
+use xrunner::camera::Camera;
+use xrunner::config::ConfigError;
+use xrunner::input::Input;
+use xrunner::map::{Map, MapError};
+use xrunner::map_renderer::MapRenderer;
+use xrunner::player::Player;
+use xrunner::command::Command;
+use xrunner::editor::{Editor, EditorAction};
+use xrunner::gamepad::Gamepads;
+use xrunner::ghost::Ghost;
+use xrunner::menu::{Menu, MenuChoice};
+use xrunner::particles::{Particles, Trigger};
+use xrunner::replay::Replay;
+use xrunner::save::{SaveGame, Settings};
+use xrunner::speedrun::{format_ticks, Run};
+use xrunner::ticker::Ticker;
+use xrunner::tiles::TileSet;
+use xrunner::watch::Watcher;
+use xrunner::world::{map_file, map_names, Event, World, FIRST_MAP};
+
 use std::path::Path;
+use std::rc::Rc;
 
-mod map;
-mod player;
-mod command;
+// Longest frame we simulate, so a stall doesn't make us catch up forever.
+const MAX_FRAME_TIME: f32 = 0.25;
 
-// Bring Player and Map into scope
-use map::Map;
-use player::Player;
-use command::Command;
+const MAIN_MENU: [&str; 4] = ["Play", "Select level", "Settings", "Quit"];
+const PAUSE_MENU: [&str; 3] = ["Resume", "Restart level", "Quit to menu"];
 
 // Helper enum for storing what state the game is in.
 enum GameState {
+	// The title screen.
+	MainMenu(Menu),
+	// Pick an unlocked map to play, holds the map of every item.
+	LevelSelect(Menu, Vec<String>),
 	GamePlay,
+	// Game play stopped with the pause menu over it.
+	Paused(Menu),
+	// The bindings of every action, holds the action waiting for a key or button to bind if any.
+	Settings(Menu, Option<Action>),
+	// Painting the current map, Tab switches between it and game play.
+	Editor,
 	WinScreen,
+	// A map failed to load, holds the command to retry once the file is fixed.
+	MapError(MapError, Command),
+	// A file the game needs besides the maps failed to load.
+	ConfigError(ConfigError),
 	Exit,
 }
 
@@ -21,78 +58,520 @@ enum GameState {
 async fn main() {
 
 	// Initial state
-	let mut current_state = GameState::GamePlay;
-	let mut current_map: String = String::from("map1.txt");
+	let mut current_state = GameState::MainMenu(open_menu("XRunner", &MAIN_MENU, 0));
+	let mut current_map: String = String::from(FIRST_MAP);
+
+	// Watch a recorded run instead of reading the keyboard, `xrunner --replay file`.
+	let mut playback: Option<Replay> = None;
+	if let Some(path) = replay_arg() {
+		match Replay::load(&path) {
+			Ok(replay) => {
+				current_map = replay.map.clone();
+				playback = Some(replay);
+				current_state = GameState::GamePlay;
+			}
+			Err(err) => current_state = GameState::ConfigError(err),
+		}
+	}
+
+	// Record every run so it can be attached to a bug report.
+	let seed = match &playback {
+		Some(replay) => replay.seed,
+		None => miniquad::date::now() as u64,
+	};
+	rand::srand(seed);
+	let mut recording = Replay::new(&current_map, seed);
+	let mut tick: usize = 0;
+	// Play-testing swaps in an edited map, until a map is started from disk again the recording
+	// can't be played back and no progress is kept.
+	let mut edited = false;
+
+	// Load the tile sheet used to draw maps.
+	let mut map_renderer = match MapRenderer::load().await {
+		Ok(renderer) => renderer,
+		Err(err) => {
+			current_state = GameState::ConfigError(err);
+			MapRenderer::empty()
+		}
+	};
+
+	// Load what the map characters mean.
+	let tile_set = match TileSet::from_file(TileSet::PATH) {
+		Ok(tile_set) => Rc::new(tile_set),
+		Err(err) => {
+			if !matches!(current_state, GameState::ConfigError(..)) {
+				current_state = GameState::ConfigError(err);
+			}
+			Rc::new(TileSet::default())
+		}
+	};
+
+	// Progress and settings of earlier runs. A save that can't be read is moved aside rather than overwritten.
+	let mut save = match SaveGame::load(SaveGame::PATH) {
+		Ok(save) => save,
+		Err(ConfigError::MissingFile { .. }) => SaveGame::default(),
+		Err(err) => {
+			let aside = format!("{}.bad", SaveGame::PATH);
+			eprintln!("{}, moved it to {} and starting a new save", err, aside);
+			if let Err(err) = std::fs::rename(SaveGame::PATH, &aside) {
+				eprintln!("Could not move {}: {}", SaveGame::PATH, err);
+			}
+			SaveGame::default()
+		}
+	};
+
+	// Sound effects and the music of the map being played, at the volumes picked in the settings.
+	let mut audio = Audio::load(&save.settings).await;
 
-	// Create a player
-	let mut player = Player::new().await;
+	// The keys and gamepad buttons of every action, the shipped ones with the player's changes read
+	// over them. Bindings that can't be read are reported and left out, changes on the settings
+	// screen are written to the player's file only.
+	let default_bindings = match Bindings::load(Bindings::PATH) {
+		Ok(bindings) => bindings,
+		Err(ConfigError::MissingFile { .. }) => Bindings::default(),
+		Err(err) => {
+			eprintln!("{}, using the default bindings", err);
+			Bindings::default()
+		}
+	};
+	let mut bindings = match default_bindings.load_overrides(Bindings::OVERRIDES_PATH) {
+		Ok(bindings) => bindings,
+		Err(ConfigError::MissingFile { .. }) => default_bindings.clone(),
+		Err(err) => {
+			eprintln!("{}, using the default bindings", err);
+			default_bindings.clone()
+		}
+	};
+
+	// Sparks, dust and embers drawn over the map, the game runs without them if they can't be read.
+	let mut particles = match Particles::load(Particles::PATH) {
+		Ok(particles) => particles,
+		Err(err) => {
+			eprintln!("{}, playing without particles", err);
+			Particles::default()
+		}
+	};
+
+	// Load a map, the player and the entities on it
+	let mut world = match World::load(&current_map, tile_set.clone()) {
+		Ok(world) => world,
+		Err(err) => {
+			if !matches!(current_state, GameState::ConfigError(..)) {
+				current_state = GameState::MapError(err, Command::ReloadMap());
+			}
+			World::from_map(&current_map, Map::empty(), tile_set.clone())
+		}
+	};
+
+	// Commands queued outside of game play, executed on the next game play frame.
+	let mut pending_commands: Vec<Command> = Vec::new();
+
+	// Menus are driven by the keyboard or any gamepad.
+	let mut gamepads = Gamepads::new();
+	// A map picked in a menu, started fresh on the next frame.
+	let mut start_level: Option<String> = None;
+	// Restarting from the pause menu holds R for a tick, so it is recorded like any other restart.
+	let mut restart_requested = false;
+	// The map being played and the coins held when it was entered or restarted, to score it when it is left.
+	let mut level_map = world.map_name.clone();
+	let mut level_coins = world.player.coins();
+	// Times the run in ticks and splits whenever a portal is taken.
+	let mut run = Run::new(&world.map_name);
+	let mut new_best = false;
+	// The player's pass through the current map, and the fastest pass from disk to race against.
+	let mut pass = Ghost::new(&world.map_name);
+	let mut ghost = best_ghost(&world.map_name);
 
-	// Load a map
-	let mut game_map = Map::from_file(map_file(&current_map)).await;
+	// Follow the player around maps larger than the window.
+	let mut camera = Camera::new(screen_width(), screen_height());
+	camera.snap(world.player.bounds(), &world.map);
+
+	// Time not yet simulated, carried over to the next frame.
+	let mut ticker = Ticker::default();
+
+	// The map editor, kept while switching to game play so unsaved changes survive play-testing.
+	let mut editor: Option<Editor> = None;
+
+	// Swap in maps and sprites edited beside the running game, failed reloads are shown in the HUD
+	// until the file is read fine again.
+	let mut watcher = Watcher::new(&["maps", "sprites"]);
+	let mut next_poll = get_time() + Watcher::POLL_INTERVAL;
+	let mut sprite_error: Option<String> = None;
+	let mut map_error: Option<String> = None;
+	// The current map changed on disk while paused, in a menu or the editor, reloaded once play goes on.
+	let mut reload_pending = false;
+
+	// Closing the window goes through GameState::Exit, so the recording is saved.
+	prevent_quit();
 
 	// Main loop.
 	loop {
 
+		gamepads.update();
+		if is_quit_requested() {
+			current_state = GameState::Exit;
+		}
+
+		// Start a new run on the picked map, the recording starts over with it.
+		if let Some(map_name) = start_level.take() {
+			if playback.is_none() && !edited && !recording.is_empty() {
+				save_replay(&recording);
+			}
+			world = match World::load(&map_name, tile_set.clone()) {
+				Ok(world) => {
+					current_state = GameState::GamePlay;
+					world
+				}
+				Err(err) => {
+					current_state = GameState::MapError(err, Command::ReloadMap());
+					World::from_map(&map_name, Map::empty(), tile_set.clone())
+				}
+			};
+			playback = None;
+			edited = false;
+			reload_pending = false;
+			map_error = None;
+			recording = Replay::new(&map_name, seed);
+			tick = 0;
+			pending_commands.clear();
+			ticker = Ticker::default();
+			camera.snap(world.player.bounds(), &world.map);
+			run = Run::new(&map_name);
+			pass = Ghost::new(&map_name);
+			ghost = best_ghost(&map_name);
+			particles.clear();
+			level_map = map_name;
+			level_coins = world.player.coins();
+		}
+
+		if playback.is_none() && get_time() >= next_poll {
+			next_poll = get_time() + Watcher::POLL_INTERVAL;
+			for path in watcher.poll() {
+				if path == Path::new(MapRenderer::SPRITE_PATH) {
+					sprite_error = map_renderer.reload().await.err().map(|err| err.to_string());
+				}
+				if let Some(open) = editor.as_mut().filter(|open| path == Path::new(&map_file(&open.map_name))) {
+					if let Err(err) = open.reload() {
+						open.status = format!("could not reload: {}", err);
+					}
+				}
+				let current = match &current_state {
+					GameState::MapError(_, Command::LoadMap(file_name, _)) => file_name.clone(),
+					_ => world.map_name.clone(),
+				};
+				if path == Path::new(&map_file(&current)) {
+					match &current_state {
+						// try again now the broken map was edited.
+						GameState::MapError(_, retry) => {
+							pending_commands.push(retry.clone());
+							current_state = GameState::GamePlay;
+						}
+						_ => reload_pending = true,
+					}
+				}
+			}
+		}
+
+		if reload_pending && playback.is_none() && matches!(current_state, GameState::GamePlay) {
+			reload_pending = false;
+			match world.reload() {
+				Ok(kept) => {
+					if !kept {
+						camera.snap(world.player.bounds(), &world.map);
+					}
+					map_error = None;
+				}
+				Err(err) => map_error = Some(err.to_string()),
+			}
+		}
+
 		// Do different rendering depending on state.
-		match current_state {
+		match &mut current_state {
+			GameState::MainMenu(menu) => {
+				audio.play_music(None).await;
+				clear_background(DARKBLUE);
+				menu.draw();
+
+				match menu.update(&gamepads) {
+					Some(MenuChoice::Picked(index)) => match MAIN_MENU[index] {
+						"Play" => start_level = Some(save.last_unlocked().to_string()),
+						"Select level" => current_state = level_select(&save),
+						"Settings" => current_state = settings(&bindings, &save.settings, 0),
+						_ => current_state = GameState::Exit,
+					},
+					Some(MenuChoice::Back) => current_state = GameState::Exit,
+					None => {}
+				}
+			}
+			GameState::LevelSelect(menu, maps) => {
+				clear_background(DARKBLUE);
+				menu.draw();
+
+				match menu.update(&gamepads) {
+					Some(MenuChoice::Picked(index)) => start_level = maps.get(index).cloned(),
+					Some(MenuChoice::Back) => current_state = GameState::MainMenu(open_menu("XRunner", &MAIN_MENU, 1)),
+					None => {}
+				}
+			}
 			GameState::GamePlay => {
+				audio.play_music(world.map.music.as_deref()).await;
 
-				clear_background(SKYBLUE);
+				// Open the editor on the current map, keeping unsaved changes if it was edited before.
+				if playback.is_none() && is_key_pressed(KeyCode::Tab) {
+					let start = (world.player.x, world.player.y);
+					match editor.as_mut().filter(|open| open.map_name == world.map_name) {
+						Some(open) => {
+							open.set_start(start);
+							current_state = GameState::Editor;
+						}
+						None => match Editor::open(&world.map_name, tile_set.clone(), start) {
+							Ok(open) => {
+								editor = Some(open);
+								current_state = GameState::Editor;
+							}
+							Err(err) => current_state = GameState::MapError(err, Command::ReloadMap()),
+						},
+					}
+					next_frame().await;
+					continue;
+				}
 
-				// Update the player
-				let mut commands = player.update(&game_map);
+				// Pause on Escape or the gamepad's start button, unless they were rebound.
+				if bindings.is_pressed(Action::Pause, &gamepads) {
+					current_state = GameState::Paused(open_menu("Paused", &PAUSE_MENU, 0));
+				}
 
-				// Draw the map
-				game_map.draw();
-				player.draw();
+				// Run the simulation in fixed ticks, however long the frame took.
+				ticker.add(get_frame_time().min(MAX_FRAME_TIME));
+				while matches!(current_state, GameState::GamePlay) && ticker.next_tick() {
 
-				draw_debug(&mut player);
+					// Read input, or take it from the replay, and update the player
+					let mut input = match &playback {
+						Some(replay) => replay.input(tick).unwrap_or_default(),
+						None => bindings.input(&gamepads),
+					};
+					if restart_requested {
+						input.0 |= Input::RESET;
+						restart_requested = false;
+					}
+					recording.record(input);
+					tick += 1;
+					run.tick();
 
-				commands.extend(handle_keyboard_input(&mut player, &game_map));
+					let commands: Vec<Command> = std::mem::take(&mut pending_commands);
+					pass.record(&world.player);
+					let before = world.player.clone();
+					let events = world.tick(input, commands);
+					camera.update(world.player.bounds(), &world.map);
+					for effect in Effect::of_tick(&before, &world.player, &events) {
+						audio.play(effect);
+					}
+					for (trigger, area) in Trigger::of_tick(&before, &world.player, &events, world.map.tile_set()) {
+						particles.emit(trigger, area, Player::TICK_TIME);
+					}
 
-				// Execute commands.
-				for command in commands {
-					match command {
-						Command::RemoveEntity(ch, tile_x, tile_y) => {
-							println!("Command::RemoveEntity");
-							game_map.remove_entity(ch, tile_x, tile_y);
-						},
-						Command::LoadMap(file_name, exit_portal) => {
-							println!("Command::LoadMap");
-							if !map_exists(&file_name) {
-								current_state = GameState::WinScreen;
-							} else {
-								current_map = file_name;
-								game_map = Map::from_file(map_file(&current_map)).await;
-								match game_map.find_portal_coordinates(exit_portal) {
-									Some((x, y)) => {
-										player.set_spawn_pos((x, y));
-									},
-									None => {
-										player.set_spawn_pos((Map::TILE_SIZE, Map::TILE_SIZE));
+					// React to what the commands did, maps left through a portal count as completed
+					// unless watching a replay or play-testing.
+					let keep_progress = playback.is_none() && !edited;
+					for event in events {
+						match event {
+							Event::EnteredPortal { map, .. } => {
+								camera.snap(world.player.bounds(), &world.map);
+								particles.clear();
+								run.split(&level_map);
+								if keep_progress {
+									complete_level(&mut save, &level_map, world.player.coins() - level_coins, Some(&map));
+									keep_pass(&pass, ghost.as_ref());
+								}
+								pass = Ghost::new(&map);
+								ghost = best_ghost(&map);
+								level_map = map;
+								level_coins = world.player.coins();
+							}
+							// the player falls off the map, the camera follows until the respawn.
+							Event::Died { .. } => {}
+							// the coins are back to what they were at the last checkpoint or when the
+							// level was entered. Past a checkpoint the pass goes on so it stays whole.
+							Event::Respawned | Event::Reset => {
+								camera.snap(world.player.bounds(), &world.map);
+								if world.checkpoint().is_none() {
+									pass = Ghost::new(&world.map_name);
+								}
+							}
+							Event::Won { .. } => {
+								run.finish(&level_map);
+								new_best = false;
+								if keep_progress {
+									save_replay(&recording);
+									let path = format!("splits/{}-{}.csv", run.start.trim_end_matches(".txt"), miniquad::date::now() as u64);
+									if let Err(err) = run.export(&path, save.personal_best(&run.start)) {
+										eprintln!("Could not export splits to {}: {}", path, err);
 									}
+									new_best = save.record_run(&run);
+									complete_level(&mut save, &level_map, world.player.coins() - level_coins, None);
+									keep_pass(&pass, ghost.as_ref());
 								}
-								// reset player position.
-								player.reset();
+								current_state = GameState::WinScreen;
 							}
-						},
-						Command::ResetMap() => {
-							println!("Command::ResetMap");
-							game_map = Map::from_file(map_file(&current_map)).await;
-							// reset player position.
-							player.reset();
-						},
-						Command::Exit() => {
-							println!("Command::Exit");
-							current_state = GameState::Exit;
+							Event::Removed { .. } | Event::Checkpoint { .. } => {}
+							Event::Exit => {
+								current_state = GameState::Exit;
+							}
+							Event::Failed(err, retry) => {
+								current_state = GameState::MapError(err, retry);
+							}
+						}
+					}
+
+					// Stop ticking if a command left game play.
+					if !matches!(current_state, GameState::GamePlay) {
+						break;
+					}
+				}
+
+				let alpha = ticker.alpha();
+				particles.update(get_frame_time().min(MAX_FRAME_TIME), &world.entities, camera.view(alpha));
+				draw_world(&world, &map_renderer, &camera, ghost.as_ref().map(|ghost| (ghost, pass.len())), &particles, alpha);
+
+				// The HUD stays in screen coordinates.
+				set_default_camera();
+				draw_debug(&world.player, world.deaths, &bindings);
+				run.draw(save.personal_best(&run.start));
+				for (line, err) in [&map_error, &sprite_error].into_iter().flatten().enumerate() {
+					draw_text(format!("Reload failed: {}", err).as_str(), 20.0, 68.0 + line as f32 * 24.0, 24.0, RED);
+				}
+				if let Some(replay) = &playback {
+					draw_text(format!("Replay: tick {} of {}", tick.min(replay.len()), replay.len()).as_str(), 20.0, 44.0, 24.0, WHITE);
+				}
+			}
+			GameState::Paused(menu) => {
+				// The game stays frozen behind the menu.
+				draw_world(&world, &map_renderer, &camera, ghost.as_ref().map(|ghost| (ghost, pass.len())), &particles, ticker.alpha());
+				set_default_camera();
+				draw_rectangle(0.0, 0.0, screen_width(), screen_height(), Color::new(0.0, 0.0, 0.0, 0.6));
+				menu.draw();
+
+				let choice = if bindings.is_pressed(Action::Pause, &gamepads) {
+					Some(MenuChoice::Back)
+				} else {
+					menu.update(&gamepads)
+				};
+				match choice {
+					Some(MenuChoice::Picked(index)) => match PAUSE_MENU[index] {
+						"Resume" => current_state = GameState::GamePlay,
+						"Restart level" => {
+							restart_requested = true;
+							current_state = GameState::GamePlay;
+						}
+						_ => current_state = GameState::MainMenu(open_menu("XRunner", &MAIN_MENU, 0)),
+					},
+					Some(MenuChoice::Back) => current_state = GameState::GamePlay,
+					None => {}
+				}
+			}
+			GameState::Settings(menu, capturing) => {
+				clear_background(DARKBLUE);
+				menu.draw();
+
+				match *capturing {
+					// Bind whatever is pressed next, Escape keeps the old bindings.
+					Some(action) => {
+						draw_text(format!("Press a key, button or stick for {}, or ESC to cancel", action.name()).as_str(), 20.0, screen_height() - 40.0, 30.0, YELLOW);
+						if is_key_pressed(KeyCode::Escape) {
+							*capturing = None;
+						} else if let Some(binding) = Binding::capture(&gamepads) {
+							bindings.bind(action, binding);
+							save_bindings(&bindings, &default_bindings);
+							current_state = settings(&bindings, &save.settings, menu.selected);
+						}
+					}
+					None => match menu.update(&gamepads) {
+						Some(MenuChoice::Picked(index)) if index < Action::ALL.len() => *capturing = Some(Action::ALL[index]),
+						// the volumes go up a step at a time, wrapping around to silent.
+						Some(MenuChoice::Picked(index)) if index < Action::ALL.len() + 2 => {
+							let volume = if index == Action::ALL.len() {
+								&mut save.settings.music_volume
+							} else {
+								&mut save.settings.effects_volume
+							};
+							*volume = Settings::step_volume(*volume);
+							audio.set_volumes(&save.settings);
+							audio.play(Effect::Coin);
+							write_save(&save);
+							current_state = settings(&bindings, &save.settings, index);
+						}
+						Some(MenuChoice::Picked(index)) if index == Action::ALL.len() + 2 => {
+							bindings = default_bindings.clone();
+							save_bindings(&bindings, &default_bindings);
+							current_state = settings(&bindings, &save.settings, index);
 						}
+						Some(_) => current_state = GameState::MainMenu(open_menu("XRunner", &MAIN_MENU, 2)),
+						None => {}
+					},
+				}
+			}
+			GameState::Editor => {
+				let Some(open) = editor.as_mut() else {
+					current_state = GameState::GamePlay;
+					continue;
+				};
+
+				// Play the edited map as it is, saved or not.
+				if let Some(EditorAction::PlayTest(x, y)) = open.update(&mut camera) {
+					match open.play_test(x, y) {
+						Ok(test) => {
+							world = test;
+							run = Run::new(&world.map_name);
+							pass = Ghost::new(&world.map_name);
+							ghost = best_ghost(&world.map_name);
+							camera.snap(world.player.bounds(), &world.map);
+							ticker = Ticker::default();
+							edited = true;
+							reload_pending = false;
+							current_state = GameState::GamePlay;
+						}
+						Err(err) => open.status = format!("can't play-test: {}", err),
 					}
 				}
+
+				open.draw(&map_renderer, &camera);
 			}
 			GameState::WinScreen => {
 				// Win screen logic and rendering here
 				clear_background(GREEN);
-				draw_text("You Won! Press ESC to Exit", 100.0, 100.0, 30.0, BLACK);
+				draw_text("You Won! Press Enter for the menu or ESC to Exit", 100.0, 100.0, 30.0, BLACK);
+				draw_text(format!("Time {}{}", format_ticks(run.ticks()), if new_best { ", a new personal best!" } else { "" }).as_str(), 100.0, 140.0, 30.0, BLACK);
+
+				if is_key_pressed(KeyCode::Enter) || gamepads.is_pressed(Button::South) {
+					current_state = GameState::MainMenu(open_menu("XRunner", &MAIN_MENU, 0));
+				} else if is_key_pressed(KeyCode::Escape) {
+					break;
+				}
+			}
+			GameState::MapError(err, retry) => {
+				clear_background(MAROON);
+				draw_text(err.to_string().as_str(), 100.0, 140.0, 24.0, WHITE);
+
+				let failed_map: &str = match retry {
+					Command::LoadMap(file_name, _) => file_name,
+					_ => &world.map_name,
+				};
+				draw_text(format!("Could not load map {}", map_file(failed_map)).as_str(), 100.0, 100.0, 30.0, WHITE);
+				draw_text("Fix the file and press R to retry, or press ESC to exit", 100.0, 180.0, 24.0, WHITE);
+				if is_key_pressed(KeyCode::R) {
+					pending_commands.push(retry.clone());
+					current_state = GameState::GamePlay;
+				}
+
+				if is_key_pressed(KeyCode::Escape) {
+					break;
+				}
+			}
+			GameState::ConfigError(err) => {
+				clear_background(MAROON);
+				draw_text("Could not load game data", 100.0, 100.0, 30.0, WHITE);
+				draw_text(err.to_string().as_str(), 100.0, 140.0, 24.0, WHITE);
+				draw_text("Press ESC to exit", 100.0, 180.0, 24.0, WHITE);
 
 				if is_key_pressed(KeyCode::Escape) {
 					break;
@@ -106,43 +585,112 @@ async fn main() {
 		// Next frame
 		next_frame().await;
 	}
+
+	if playback.is_none() && !edited {
+		save_replay(&recording);
+	}
 }
 
-fn handle_keyboard_input(player: &mut Player, game_map: &Map) -> Vec<Command> {
+// keep the recording in replays/ so it can be attached to a bug report.
+fn save_replay(recording: &Replay) {
+	if let Err(err) = recording.save(Replay::LAST_PATH) {
+		eprintln!("Could not save replay {}: {}", Replay::LAST_PATH, err);
+	}
+}
 
-	let mut ret: Vec<Command> = Vec::new();
+// the unlocked maps with the best coins collected on the completed ones.
+fn level_select(save: &SaveGame) -> GameState {
+	let maps: Vec<String> = map_names().into_iter().filter(|map| save.is_unlocked(map)).collect();
+	let items = maps.iter()
+		.map(|map| match save.best_coins.get(map) {
+			Some(best) if save.is_completed(map) => format!("{}  (best {} coins)", map, best),
+			_ => map.clone(),
+		})
+		.collect();
+	GameState::LevelSelect(Menu::new(&format!("Select level - {} coins in total", save.total_coins()), items), maps)
+}
 
-	// Handle all keyboard interactions.
-	if is_key_down(KeyCode::Escape) {
-		ret.push(Command::Exit());
-	}
-	if is_key_down(KeyCode::Space) {
-		player.jump(&game_map);
-	}
-	if is_key_down(KeyCode::Left) {
-		player.move_left()
-	}
-	if is_key_down(KeyCode::Right) {
-		player.move_right()
+// the bindings of every action and the volumes with the item at `selected` selected.
+fn settings(bindings: &Bindings, volumes: &Settings, selected: usize) -> GameState {
+	let mut items: Vec<String> = Action::ALL.iter()
+		.map(|&action| format!("{}: {}", action.name(), bindings.labels(action)))
+		.collect();
+	items.push(format!("Music volume: {}%", (volumes.music_volume * 100.0).round()));
+	items.push(format!("Effects volume: {}%", (volumes.effects_volume * 100.0).round()));
+	items.push(String::from("Reset to defaults"));
+	items.push(String::from("Back"));
+	let mut menu = Menu::new("Settings - Enter to change", items);
+	menu.selected = selected;
+	GameState::Settings(menu, None)
+}
+
+// Write the bindings changed from `defaults` out, they stay in effect if that fails.
+fn save_bindings(bindings: &Bindings, defaults: &Bindings) {
+	if let Err(err) = bindings.save_overrides(defaults, Bindings::OVERRIDES_PATH) {
+		eprintln!("Could not save {}: {}", Bindings::OVERRIDES_PATH, err);
 	}
-	if is_key_down(KeyCode::Up) {
-		player.move_up(&game_map)
+}
+
+// Note a map left through a portal in the save game and write it out.
+fn complete_level(save: &mut SaveGame, map: &str, coins: i32, next: Option<&str>) {
+	save.complete_level(map, coins, next);
+	write_save(save);
+}
+
+fn write_save(save: &SaveGame) {
+	if let Err(err) = save.save(SaveGame::PATH) {
+		eprintln!("Could not save {}: {}", SaveGame::PATH, err);
 	}
-	if is_key_down(KeyCode::Down) {
-		player.move_down()
+}
+
+// a menu with the item at `selected` selected, e.g. the one the player came back from.
+fn open_menu(title: &str, items: &[&str], selected: usize) -> Menu {
+	let mut menu = Menu::new(title, items.iter().map(|item| item.to_string()).collect());
+	menu.selected = selected;
+	menu
+}
+
+// Draw the map, entities, ghost at the given tick, player and particles, the player, ghost and camera
+// are drawn `alpha` of the way between the last two ticks.
+fn draw_world(world: &World, map_renderer: &MapRenderer, camera: &Camera, ghost: Option<(&Ghost, usize)>, particles: &Particles, alpha: f32) {
+	clear_background(SKYBLUE);
+	camera.activate(alpha);
+	map_renderer.draw(&world.map, camera.view(alpha));
+	world.entities.draw(map_renderer.sprite(), alpha, camera.view(alpha));
+	if let Some((ghost, tick)) = ghost {
+		ghost.draw(map_renderer.sprite(), world.map.tile_set(), tick, alpha);
 	}
-	if is_key_down(KeyCode::R) {
-		ret.push(Command::ResetMap());
+	world.player.draw(map_renderer.sprite(), world.map.tile_set(), alpha);
+	particles.draw();
+}
+
+// the fastest pass through `map` on disk, if any.
+fn best_ghost(map: &str) -> Option<Ghost> {
+	match Ghost::load(&Ghost::path(map)) {
+		Ok(ghost) => Some(ghost),
+		Err(ConfigError::MissingFile { .. }) => None,
+		Err(err) => {
+			eprintln!("{}", err);
+			None
+		}
 	}
+}
 
-	return ret;
+// Keep a pass that just left its map if it was the fastest yet.
+fn keep_pass(pass: &Ghost, best: Option<&Ghost>) {
+	if pass.beats(best) {
+		let path = Ghost::path(&pass.map);
+		if let Err(err) = pass.save(&path) {
+			eprintln!("Could not save ghost {}: {}", path, err);
+		}
+	}
 }
 
-fn draw_debug(player: &mut Player) {
+fn draw_debug(player: &Player, deaths: u32, bindings: &Bindings) {
 	let fps = get_fps();
 	// Debug output
 	draw_text(
-		format!("Coins: {}, Press R to restart level. Press ESC to quit. Gravity: {} vx: {} vy: {} g: {} fps: {}", player.coins(), if player.gravity() > 0.0 { "on" } else { "off" }, player.vx(), player.vy(), player.gravity(), fps).as_str(),
+		format!("Coins: {}, Deaths: {}, Press {} to restart level. Press Tab to edit. Press {} to pause. Gravity: {} vx: {} vy: {} g: {} fps: {}", player.coins(), deaths, bindings.labels(Action::Reset), bindings.labels(Action::Pause), if player.gravity() > 0.0 { "on" } else { "off" }, player.vx(), player.vy(), player.gravity(), fps).as_str(),
 		20.0,
 		20.0,
 		24.0,
@@ -150,13 +698,14 @@ fn draw_debug(player: &mut Player) {
 	);
 }
 
-fn map_file(file_name: &String) -> String {
-	format!("maps/{}", file_name)
-}
-
-fn map_exists(file_name: &String) -> bool {
-	let path_string = map_file(file_name);
-	return Path::new(&path_string).exists()
+fn replay_arg() -> Option<String> {
+	let mut args = std::env::args().skip(1);
+	while let Some(arg) = args.next() {
+		if arg == "--replay" {
+			return args.next();
+		}
+	}
+	None
 }
 
 /// Optional: window configuration function
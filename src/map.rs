@@ -1,339 +1,602 @@
+use crate::autotile::{neighbour_mask, TileSprite};
+use crate::tiles::{TileSet, Touch};
+
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::rc::Rc;
+
+// Everything that can go wrong while loading a map. Lines and columns are 1-based so
+// they match what a text editor shows.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MapError {
+	MissingFile { path: String, reason: String },
+	RaggedRow { line: usize, column: usize, expected: usize, found: usize },
+	UnknownTile { tile: char, line: usize, column: usize },
+	MalformedMetadata { line: usize, column: usize, text: String },
+	PortalWithoutMapping { portal: char, line: usize, column: usize },
+}
+
+impl fmt::Display for MapError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			MapError::MissingFile { path, reason } => {
+				write!(f, "could not read {}: {}", path, reason)
+			}
+			MapError::RaggedRow { line, column, expected, found } => {
+				write!(f, "line {}, column {}: row has {} tiles, expected {}", line, column, found, expected)
+			}
+			MapError::UnknownTile { tile, line, column } => {
+				write!(f, "line {}, column {}: unknown tile '{}'", line, column, tile)
+			}
+			MapError::MalformedMetadata { line, column, text } => {
+				write!(f, "line {}, column {}: malformed metadata '{}', expected '[x] target' or 'music file'", line, column, text)
+			}
+			MapError::PortalWithoutMapping { portal, line, column } => {
+				write!(f, "line {}, column {}: portal '{}' has no '[{}] target' mapping", line, column, portal, portal)
+			}
+		}
+	}
+}
+
+impl std::error::Error for MapError {}
+
+// Where a ray cast through the map hit a tile.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayHit {
+	pub distance: f32,
+	pub tile_x: usize,
+	pub tile_y: usize,
+	// outward normal of the tile face the ray entered through, (0, 0) if it started inside the tile.
+	pub normal: (f32, f32),
+}
+
+// An axis-aligned box in pixels, (x, y) is the top left corner.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+	pub x: f32,
+	pub y: f32,
+	pub w: f32,
+	pub h: f32,
+}
 
-use macroquad::prelude::*;
-use std::collections::HashMap;
+impl Aabb {
 
+	// true if the boxes share some area, boxes that only touch don't overlap.
+	pub fn overlaps(&self, other: &Aabb) -> bool {
+		self.x < other.x + other.w && other.x < self.x + self.w
+			&& self.y < other.y + other.h && other.y < self.y + self.h
+	}
+
+	// the box shrunk by `amount` on every side.
+	pub fn inset(&self, amount: f32) -> Aabb {
+		Aabb { x: self.x + amount, y: self.y + amount, w: self.w - 2.0 * amount, h: self.h - 2.0 * amount }
+	}
+}
+
+// Outcome of moving a box through the map with `Map::sweep`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Sweep {
+	// how far the box could move on each axis.
+	pub motion: (f32, f32),
+	// contact normal per axis, e.g. (0, -1) when landing on a floor, (0, 0) if nothing was touched.
+	pub normal: (f32, f32),
+	// the tiles the box was stopped by.
+	pub touched: Vec<(usize, usize)>,
+}
+
+// The tiles and portal mappings of a level along with the collision queries on them.
+// Holds no graphics resources, see `MapRenderer` for drawing.
+#[derive(Clone)]
 pub struct Map {
 	pub tiles: Vec<Vec<char>>,
 	pub width: usize,
 	pub height: usize,
-	sprite_bg1: Texture2D,
-	mappings: HashMap<char, String>,
+	// portal mappings in the order of the file, so saving writes them back the same way.
+	mappings: Vec<(char, String)>,
+	// file in music/ looped while the map is played, from a "music file" metadata line.
+	pub music: Option<String>,
+	tile_set: Rc<TileSet>,
+	// sprites picked by autotiling, kept up to date when tiles change.
+	autotiles: Vec<Vec<Option<TileSprite>>>,
 }
 
 impl Map {
 
 	pub const TILE_SIZE: f32 = 32.0;
 
-	pub async fn from_file(path: String) -> Self {
-
-		println!("Loading map {}", path);
+	// How far a box may overlap or float off a tile edge and still count as touching it, this
+	// absorbs float rounding so boxes resting on a tile stay on it.
+	pub const SKIN: f32 = 0.01;
 
-		let sprite_bg1 = load_texture("sprites/bg1.png").await.unwrap();
-		sprite_bg1.set_filter(FilterMode::Nearest);
+	// How far a blocked box is moved across its motion to line up with a gap, a box as wide as a tile
+	// rarely lines up with one exactly.
+	pub const NUDGE: f32 = 1.0;
 
-		let content = std::fs::read_to_string(path).unwrap();
+	pub fn from_file(path: &str, tile_set: Rc<TileSet>) -> Result<Self, MapError> {
+		let content = std::fs::read_to_string(path).map_err(|err| MapError::MissingFile {
+			path: path.to_string(),
+			reason: err.to_string(),
+		})?;
 
-		let (tiles, mappings) = Self::parse_map(&content);
-
-		let height = tiles.len();
-		let width = if height > 0 {
-			tiles[0].len()
-		} else {
-			0
-		};
+		Self::parse_map(&content, tile_set)
+	}
 
+	// An empty map, used as a placeholder when no map could be loaded.
+	pub fn empty() -> Self {
 		Map {
-			tiles,
-			width,
-			height,
-			sprite_bg1,
-			mappings,
+			tiles: Vec::new(),
+			width: 0,
+			height: 0,
+			mappings: Vec::new(),
+			music: None,
+			tile_set: Rc::new(TileSet::default()),
+			autotiles: Vec::new(),
 		}
 	}
 
-	fn parse_map(content: &str) -> (Vec<Vec<char>>, HashMap<char, String>) {
-		let lines = content.lines();
+	// Parses a map file, every character must be defined in `tile_set`.
+	pub fn parse_map(content: &str, tile_set: Rc<TileSet>) -> Result<Self, MapError> {
 
-		// Separate map and metadata sections
+		// Separate map and metadata sections, keeping the line number of every line.
 		let mut tile_lines = Vec::new();
 		let mut metadata_lines = Vec::new();
 		let mut in_metadata = false;
 
-		for line in lines {
+		for (index, line) in content.lines().enumerate() {
 			if line.trim().is_empty() {
 				in_metadata = true;
 				continue;
 			}
 			if in_metadata {
-				metadata_lines.push(line);
+				metadata_lines.push((index + 1, line));
 			} else {
-				tile_lines.push(line);
+				tile_lines.push((index + 1, line));
 			}
 		}
 
-		// Parse the tile map
-		let tiles: Vec<Vec<char>> = tile_lines
-			.iter()
-			.map(|line| line.chars().collect())
-			.collect();
-
-		// Parse the metadata
-		let mut metadata = HashMap::new();
-		for line in metadata_lines {
-			if let Some((key_part, value_part)) = line.split_once(']') {
-				if let Some(symbol) = key_part.trim_start_matches('[').chars().next() {
-					metadata.insert(symbol, value_part.trim().to_string());
+		// Parse the tile map, every row must have the same width as the first one.
+		let mut tiles: Vec<Vec<char>> = Vec::new();
+		for &(line, text) in &tile_lines {
+			let row: Vec<char> = text.chars().collect();
+			if let Some(column) = row.iter().position(|tile| !tile_set.contains(*tile)) {
+				return Err(MapError::UnknownTile { tile: row[column], line, column: column + 1 });
+			}
+			if let Some(first) = tiles.first() {
+				if row.len() != first.len() {
+					return Err(MapError::RaggedRow {
+						line,
+						column: row.len().min(first.len()) + 1,
+						expected: first.len(),
+						found: row.len(),
+					});
 				}
 			}
+			tiles.push(row);
 		}
 
-		(tiles, metadata)
-	}
-
-	pub fn draw(&self) {
-		// Example "draw" routine using Macroquad
-		use macroquad::prelude::*;
-		for (row_index, row) in self.tiles.iter().enumerate() {
-			for (col_index, &tile) in row.iter().enumerate() {
-				let x = col_index as f32 * Self::TILE_SIZE;
-				let y = row_index as f32 * Self::TILE_SIZE;
-				match tile {
-					' ' => {
-						draw_rectangle(x, y, Self::TILE_SIZE, Self::TILE_SIZE, SKYBLUE);
-					}
-					'.' => {
-						draw_rectangle(x, y, Self::TILE_SIZE, Self::TILE_SIZE, BLUE);
-					}
-					'f' => {
-						self.texture_rot(self.sprite_bg1, x, y, 1, 7, 0.0);
-					}
-					'x' => {
-						let (u1, _u2, r1, d1, l1) = self.get_solid_tile_context(row_index, col_index);
-						if !u1 && r1 && d1 && l1 { self.texture_rot(self.sprite_bg1, x, y, 1, 0, 0.0); }
-						else if !u1 && r1 && !d1 && l1 { self.texture_rot(self.sprite_bg1, x, y, 1, 0, 0.0); }
-						else if !u1 && r1 && d1 && !l1 { self.texture_rot(self.sprite_bg1, x, y, 0, 0, 0.0); }
-						else if !u1 && !r1 && d1 && l1 { self.texture_rot(self.sprite_bg1, x, y, 2, 0, 0.0); }
-						else if !u1 && r1 && !d1 && !l1 { self.texture_rot(self.sprite_bg1, x, y, 0, 0, 0.0); }
-						else if !u1 && !r1 && !d1 && l1 { self.texture_rot(self.sprite_bg1, x, y, 2, 0, 0.0); }
-						else if !u1 && !r1 && !d1 && !l1 { self.texture_rot(self.sprite_bg1, x, y, 5, 1, 0.0); }
-						else if !u1 && !r1 && d1 && !l1 { self.texture_rot(self.sprite_bg1, x, y, 4, 0, 0.0); }
-						else if u1 && !r1 && !d1 && l1 { self.texture_rot(self.sprite_bg1, x, y, 5, 5, 0.0); }
-						else if u1 && !r1 && !d1 && !l1 { self.texture_rot(self.sprite_bg1, x, y, 4, 0, 180.0); }
-						else if u1 && !r1 && d1 && !l1 { self.texture_rot(self.sprite_bg1, x, y, 4, 5, 0.0); }
-						else if u1 && !r1 && d1 && l1 { self.texture_rot(self.sprite_bg1, x, y, 5, 5, 0.0); }
-						else if u1 && r1 && !d1 && l1 { self.texture_rot(self.sprite_bg1, x, y, 4, 5, 270.0); }
-						else if u1 && r1 && !d1 && !l1 { self.texture_rot(self.sprite_bg1, x, y, 4, 5, 270.0); }
-						else if u1 && r1 && d1 && l1 { self.texture_rot(self.sprite_bg1, x, y, 11, 1, 180.0); }
-						else if u1 && r1 && d1 && !l1 { self.texture_rot(self.sprite_bg1, x, y, 4, 5, 0.0); }
-						else { self.texture(self.sprite_bg1, x, y, 3, 0); }
-					}
-					'g' => {
-						self.texture_rot(self.sprite_bg1, x, y, 0, 7, 0.0);
-					}
-					'c' => {
-						self.texture_rot(self.sprite_bg1, x, y, 0, 6, 0.0);
-					}
-					_ => {
-						draw_rectangle(x, y, Self::TILE_SIZE, Self::TILE_SIZE, PINK);
+		// Parse the metadata
+		let mut mappings: Vec<(char, String)> = Vec::new();
+		let mut music = None;
+		for (line, text) in metadata_lines {
+			if let Some(file) = text.trim().strip_prefix("music ") {
+				music = Some(file.trim().to_string());
+				continue;
+			}
+			match Self::parse_metadata(text) {
+				Ok((symbol, target)) => {
+					// a later mapping of the same portal wins.
+					match mappings.iter_mut().find(|(known, _)| *known == symbol) {
+						Some(mapping) => mapping.1 = target,
+						None => mappings.push((symbol, target)),
 					}
 				}
+				Err(column) => {
+					return Err(MapError::MalformedMetadata { line, column, text: text.to_string() });
+				}
 			}
 		}
-	}
-
-	fn get_solid_tile_context(&self, row: usize, col: usize) -> (bool, bool, bool, bool, bool) {
 
-		let is_x = |r: isize, c: isize| -> bool {
-			if r >= 0 && c >= 0 && (r as usize) < self.height && (c as usize) < self.width {
-				self.tiles[r as usize][c as usize] == 'x'
-			} else {
-				true
+		// Every portal on the map must lead somewhere.
+		for (row_index, row) in tiles.iter().enumerate() {
+			for (col_index, tile) in row.iter().enumerate() {
+				if tile_set.touch(*tile) == Some(Touch::Portal) && !mappings.iter().any(|(symbol, _)| symbol == tile) {
+					return Err(MapError::PortalWithoutMapping {
+						portal: *tile,
+						line: tile_lines[row_index].0,
+						column: col_index + 1,
+					});
+				}
 			}
-		};
+		}
 
-		let u1 = is_x(row as isize - 1, col as isize);  // up
-		let u2 = is_x(row as isize - 2, col as isize);  // up two
-		let r1 = is_x(row as isize, col as isize + 1);  // right
-		let l1 = is_x(row as isize, col as isize - 1);  // left
-		let d1 = is_x(row as isize + 1, col as isize);  // down
-
-		(u1, u2, r1, d1, l1)
-	}
-
-	fn texture(&self, image: Texture2D, x: f32, y: f32, pos_x: i32, pos_y: i32) {
-		draw_texture_ex(
-			image,
-			x,
-			y,
-			WHITE,
-			DrawTextureParams {
-				dest_size: Some(vec2(Self::TILE_SIZE, Self::TILE_SIZE)),
-				// If your sprite has a known tile size, specify it as the source rect.
-				// For example, if it's 16x16:
-				source: Some(Rect::new(pos_x as f32 * Self::TILE_SIZE, pos_y as f32 * Self::TILE_SIZE, Self::TILE_SIZE, Self::TILE_SIZE)),
-				..Default::default()
-			},
-		);
-	}
-
-	fn texture_rot(&self, image: Texture2D, x: f32, y: f32, pos_x: i32, pos_y: i32, rotation_deg: f32) {
-		draw_texture_ex(
-			image,
-			x,
-			y,
-			WHITE,
-			DrawTextureParams {
-				dest_size: Some(vec2(Self::TILE_SIZE, Self::TILE_SIZE)),
-				source: Some(Rect::new(pos_x as f32 * Self::TILE_SIZE, pos_y as f32 * Self::TILE_SIZE, Self::TILE_SIZE, Self::TILE_SIZE)),
-				rotation: rotation_deg.to_radians(),
-				pivot: None,
-				..Default::default()
-			},
-		);
-	}
-
-	// Returns `Some((t_enter, (ix, iy)))` if there's a valid intersection in front of `ray_origin`;
-	// otherwise `None`.
-	fn ray_box_intersection(
-		ray_origin: (f32, f32),
-		ray_dir: (f32, f32),
-		box_min: (f32, f32),
-		box_max: (f32, f32),
-	) -> Option<(f32, (f32, f32))>
-	{
-		let (x0, y0) = ray_origin;
-		let (dx, dy) = ray_dir;
-		let (xmin, ymin) = box_min;
-		let (xmax, ymax) = box_max;
-
-		// Handle vertical direction = 0
-		let (t_xmin, t_xmax) = if dx.abs() < f32::EPSILON {
-			// Ray is vertical; check if within box in X
-			if x0 < xmin || x0 > xmax {
-				return None;
-			}
-			(-f32::INFINITY, f32::INFINITY)
+		let height = tiles.len();
+		let width = if height > 0 {
+			tiles[0].len()
 		} else {
-			let t1 = (xmin - x0) / dx;
-			let t2 = (xmax - x0) / dx;
-			(t1.min(t2), t1.max(t2))
+			0
 		};
 
-		// Handle horizontal direction = 0
-		let (t_ymin, t_ymax) = if dy.abs() < f32::EPSILON {
-			// Ray is horizontal; check if within box in Y
-			if y0 < ymin || y0 > ymax {
-				return None;
-			}
-			(-f32::INFINITY, f32::INFINITY)
-		} else {
-			let t3 = (ymin - y0) / dy;
-			let t4 = (ymax - y0) / dy;
-			(t3.min(t4), t3.max(t4))
+		let mut map = Map {
+			tiles,
+			width,
+			height,
+			mappings,
+			music,
+			tile_set,
+			autotiles: Vec::new(),
 		};
+		map.update_autotiles(0, 0, height, width);
 
-		// Parametric range of intersection
-		let t_enter = t_xmin.max(t_ymin);
-		let t_exit  = t_xmax.min(t_ymax);
+		Ok(map)
+	}
 
-		// We need t_exit >= t_enter and some portion >= 0 for a valid intersection ahead of origin
-		if t_exit >= t_enter && t_exit >= 0.0 {
-			let t_hit = t_enter.max(0.0);
-			let ix = x0 + t_hit * dx;
-			let iy = y0 + t_hit * dy;
-			Some((t_hit, (ix, iy)))
-		} else {
-			None
+	// Parses a "[x] target" line. On failure returns the 1-based column where it went wrong.
+	fn parse_metadata(text: &str) -> Result<(char, String), usize> {
+		let mut chars = text.chars().enumerate().skip_while(|(_, c)| c.is_whitespace());
+
+		match chars.next() {
+			Some((_, '[')) => {}
+			Some((column, _)) => return Err(column + 1),
+			None => return Err(1),
+		}
+		let symbol = match chars.next() {
+			Some((_, c)) if c != ']' && !c.is_whitespace() => c,
+			Some((column, _)) => return Err(column + 1),
+			None => return Err(text.chars().count() + 1),
+		};
+		let close = match chars.next() {
+			Some((column, ']')) => column,
+			Some((column, _)) => return Err(column + 1),
+			None => return Err(text.chars().count() + 1),
+		};
+
+		let target: String = text.chars().skip(close + 1).collect();
+		let target = target.trim();
+		if target.is_empty() {
+			return Err(close + 2);
+		}
+
+		Ok((symbol, target.to_string()))
+	}
+
+	// The sprite autotiling picked for a tile, `None` for tiles without autotile rules.
+	pub fn autotile(&self, row: usize, col: usize) -> Option<TileSprite> {
+		self.autotiles.get(row).and_then(|sprites| sprites.get(col)).copied().flatten()
+	}
+
+	// Recompute the autotile sprites of the tiles from (first_row, first_col) up to but not
+	// including (last_row, last_col).
+	fn update_autotiles(&mut self, first_row: usize, first_col: usize, last_row: usize, last_col: usize) {
+		if self.autotiles.len() != self.height {
+			self.autotiles = vec![vec![None; self.width]; self.height];
+		}
+
+		for row in first_row..last_row.min(self.height) {
+			for col in first_col..last_col.min(self.width) {
+				let sprite = self.tile_set.autotile(self.tiles[row][col]).and_then(|autotile| {
+					let mask = neighbour_mask(|dx, dy| {
+						let (r, c) = (row as i64 + dy, col as i64 + dx);
+						if r < 0 || c < 0 || r >= self.height as i64 || c >= self.width as i64 {
+							return true;
+						}
+						self.tile_set.autotile(self.tiles[r as usize][c as usize])
+							.is_some_and(|other| other.name == autotile.name)
+					});
+					autotile.pick(mask)
+				});
+				self.autotiles[row][col] = sprite;
+			}
 		}
 	}
 
 	/*
-	 * Returns the distance as a number between the point (x,y) and the closest solid in the direction (dir_x, dir_y)
+	 * Returns the distance between `start` and the closest solid tile in the direction `dir`, if it is
+	 * no further away than `distance`.
 	 * */
 	pub fn raycast(&self, start: (f32, f32), dir: (f32, f32), distance: f32) -> Option<f32> {
-		self.raycast_any(start, dir, distance, 'x').map(|(result, _, _)| result)
+		self.raycast_any(start, dir, distance, self.tile_set.solids()).map(|hit| hit.distance)
 	}
 
-	pub fn raycast_any(&self, start: (f32, f32), dir: (f32, f32), distance: f32, solid: char) -> Option<(f32, usize, usize)> {
-		// How far we step forward each iteration.
-		let step_size = 19.0_f32;
+	/*
+	 * Walks the tile grid cell by cell along the ray (Amanatides & Woo) and returns the first tile that
+	 * is one of `solids`. A ray starting inside a solid tile hits it at distance zero.
+	 * */
+	pub fn raycast_any(&self, start: (f32, f32), dir: (f32, f32), distance: f32, solids: &[char]) -> Option<RayHit> {
+		let length = (dir.0 * dir.0 + dir.1 * dir.1).sqrt();
+		if length < f32::EPSILON {
+			return None;
+		}
+		let dir = (dir.0 / length, dir.1 / length);
 
-		let mut pos = start;
-		let mut total_distance = 0.0_f32;
+		let mut tile_x = (start.0 / Self::TILE_SIZE).floor() as i64;
+		let mut tile_y = (start.1 / Self::TILE_SIZE).floor() as i64;
+
+		// which way we step through the grid, the distance along the ray to the first grid line on
+		// each axis and the distance between two grid lines on each axis.
+		let (step_x, mut next_x, delta_x) = Self::ray_axis(start.0, dir.0, tile_x);
+		let (step_y, mut next_y, delta_y) = Self::ray_axis(start.1, dir.1, tile_y);
+
+		let mut travelled = 0.0_f32;
+		let mut normal = (0.0, 0.0);
 
 		loop {
-			// Step forward along ray.
-			pos.0 += dir.0 * step_size;
-			pos.1 += dir.1 * step_size;
-			total_distance += step_size;
-
-			// If we crossed our maximum ray distance, give up.
-			if total_distance > distance {
-				break;
+			if self.tile_is_any(tile_x, tile_y, solids) {
+				return Some(RayHit {
+					distance: travelled,
+					tile_x: tile_x as usize,
+					tile_y: tile_y as usize,
+					normal,
+				});
 			}
 
-			// Check if we hit a "solid" tile.
-			if let Some((tile_pos_x, tile_pos_y, tile_x, tile_y)) = self.get_solid(pos.0, pos.1, solid) {
-				// Assume (tile_pos_x, tile_pos_y) is the top-left corner of a 32x32 tile.
-				// So if y is increasing downward, the bottom-right corner is (tile_pos_x + 32, tile_y + 32).
-				let box_min = (tile_pos_x, tile_pos_y);
-				let box_max = (tile_pos_x + Self::TILE_SIZE, tile_pos_y + Self::TILE_SIZE);
-
-				// Check for intersection using our helper.
-				if let Some((t_enter, (hit_x, hit_y))) = Map::ray_box_intersection(start, dir, box_min, box_max) {
-					// We only care about an intersection "in front" of start; also check if it’s within
-					// the distance we’ve traveled this loop (or you can check if t_enter <= distance, etc.)
-					if t_enter >= 0.0 && t_enter <= total_distance {
-						// Optionally draw or do whatever you want with (hit_x, hit_y).
-						// Return the parametric distance t_enter, or the actual Euclidean distance from `start`.
-						let dx = hit_x - start.0;
-						let dy = hit_y - start.1;
-						let intersection_dist = (dx*dx + dy*dy).sqrt();
-
-						//draw_line(start.0, start.1, start.0 + 30.0, start.1, 2.0, GREEN);
-
-						return Some((intersection_dist, tile_x, tile_y));
-					}
-				}
+			// once outside the map and moving away from it, nothing can be hit anymore.
+			if (tile_x < 0 && step_x <= 0) || (tile_x >= self.width as i64 && step_x >= 0)
+				|| (tile_y < 0 && step_y <= 0) || (tile_y >= self.height as i64 && step_y >= 0) {
+				return None;
+			}
+
+			// step into the neighbouring tile whose grid line is closest.
+			if next_x < next_y {
+				travelled = next_x;
+				tile_x += step_x;
+				next_x += delta_x;
+				normal = (-step_x as f32, 0.0);
+			} else {
+				travelled = next_y;
+				tile_y += step_y;
+				next_y += delta_y;
+				normal = (0.0, -step_y as f32);
+			}
+
+			if travelled > distance {
+				return None;
 			}
 		}
+	}
 
-		None
+	// Step direction, distance to the first grid line and distance between grid lines along one axis.
+	fn ray_axis(start: f32, dir: f32, tile: i64) -> (i64, f32, f32) {
+		if dir > 0.0 {
+			(1, ((tile + 1) as f32 * Self::TILE_SIZE - start) / dir, Self::TILE_SIZE / dir)
+		} else if dir < 0.0 {
+			(-1, (start - tile as f32 * Self::TILE_SIZE) / -dir, Self::TILE_SIZE / -dir)
+		} else {
+			(0, f32::INFINITY, f32::INFINITY)
+		}
 	}
 
-	pub fn get_solid(&self, x: f32, y: f32, solid: char) -> Option<(f32, f32, usize, usize)> {
-		let tile_x = (x / Self::TILE_SIZE).floor() as usize;
-		let tile_y = (y / Self::TILE_SIZE).floor() as usize;
+	// true if the box is inside the map and doesn't overlap any tile in `solids`, boxes resting
+	// against a tile are still free.
+	pub fn is_free(&self, bounds: Aabb, solids: &[char]) -> bool {
+		let width = self.width as f32 * Self::TILE_SIZE;
+		let height = self.height as f32 * Self::TILE_SIZE;
+		if bounds.x < 0.0 || bounds.y < 0.0 || bounds.x + bounds.w > width || bounds.y + bounds.h > height {
+			return false;
+		}
 
-		if tile_x >= self.width || tile_y >= self.height {
-			return None;
+		let inner = bounds.inset(Self::SKIN);
+		let first_x = (inner.x / Self::TILE_SIZE).floor() as i64;
+		let first_y = (inner.y / Self::TILE_SIZE).floor() as i64;
+		let last_x = ((inner.x + inner.w) / Self::TILE_SIZE).floor() as i64;
+		let last_y = ((inner.y + inner.h) / Self::TILE_SIZE).floor() as i64;
+		for tile_y in first_y..=last_y {
+			for tile_x in first_x..=last_x {
+				if self.tile_is_any(tile_x, tile_y, solids) {
+					return false;
+				}
+			}
+		}
+		true
+	}
+
+	fn tile_is_any(&self, tile_x: i64, tile_y: i64, solids: &[char]) -> bool {
+		if tile_x < 0 || tile_y < 0 || tile_x >= self.width as i64 || tile_y >= self.height as i64 {
+			return false;
 		}
+		solids.contains(&self.tiles[tile_y as usize][tile_x as usize])
+	}
 
-		match self.tiles[tile_y][tile_x] {
-			tile if tile == solid => {
-				return Some(((tile_x as f32) * Self::TILE_SIZE, (tile_y as f32) * Self::TILE_SIZE, tile_x, tile_y))
+	/*
+	 * Shortest way through open tiles from one tile to another moving up, down, left or right, as the
+	 * tiles to step on after `from`. None if `to` can't be reached within `max_steps` steps.
+	 * */
+	pub fn find_path(&self, from: (usize, usize), to: (usize, usize), max_steps: usize) -> Option<Vec<(usize, usize)>> {
+		let solids = self.solids();
+		let mut came_from: HashMap<(usize, usize), (usize, usize)> = HashMap::new();
+		let mut queue = VecDeque::from([(from, 0)]);
+		came_from.insert(from, from);
+
+		while let Some((tile, steps)) = queue.pop_front() {
+			if tile == to {
+				let mut path = Vec::new();
+				let mut current = tile;
+				while current != from {
+					path.push(current);
+					current = came_from[&current];
+				}
+				path.reverse();
+				return Some(path);
 			}
-			_ => {
-				return None;
+			if steps == max_steps {
+				continue;
+			}
+
+			for (dx, dy) in [(0, -1), (1, 0), (0, 1), (-1, 0)] {
+				let (next_x, next_y) = (tile.0 as i64 + dx, tile.1 as i64 + dy);
+				if next_x < 0 || next_y < 0 || next_x >= self.width as i64 || next_y >= self.height as i64
+					|| self.tile_is_any(next_x, next_y, solids) {
+					continue;
+				}
+				let next = (next_x as usize, next_y as usize);
+				if let Entry::Vacant(entry) = came_from.entry(next) {
+					entry.insert(tile);
+					queue.push_back((next, steps + 1));
+				}
 			}
+		}
+
+		None
+	}
+
+	/*
+	 * Moves `bounds` by `velocity` through the map, one axis at a time, stopping each axis at the first
+	 * of `solids` in the way. Tiles the box already overlaps are ignored so it can always move out of them.
+	 * A box blocked within `NUDGE` of lining up with the tile grid is lined up if that gets it further.
+	 * */
+	pub fn sweep(&self, bounds: Aabb, velocity: (f32, f32), solids: &[char]) -> Sweep {
+		let mut result = Sweep::default();
+
+		// horizontal first, then vertical from where the box ended up.
+		let (nudge_y, motion_x, normal_x, touched_x) = self.sweep_nudged(bounds, velocity.0, true, solids);
+		let moved = Aabb { x: bounds.x + motion_x, y: bounds.y + nudge_y, ..bounds };
+		let (nudge_x, motion_y, normal_y, touched_y) = self.sweep_nudged(moved, velocity.1, false, solids);
+
+		result.motion = (motion_x + nudge_x, nudge_y + motion_y);
+		result.normal = (normal_x, normal_y);
+		result.touched.extend(touched_x);
+		result.touched.extend(touched_y);
+		result
+	}
+
+	// Sweeps the box along one axis like `sweep_axis`, first returning how far it was moved across
+	// to line it up with a gap.
+	fn sweep_nudged(&self, bounds: Aabb, amount: f32, horizontal: bool, solids: &[char]) -> (f32, f32, f32, Vec<(usize, usize)>) {
+		let (motion, normal, touched) = self.sweep_axis(bounds, amount, horizontal, solids);
+		if normal == 0.0 {
+			return (0.0, motion, normal, touched);
+		}
+
+		let across = if horizontal { bounds.y } else { bounds.x };
+		let nudge = (across / Self::TILE_SIZE).round() * Self::TILE_SIZE - across;
+		if nudge == 0.0 || nudge.abs() > Self::NUDGE || self.sweep_axis(bounds, nudge, !horizontal, solids).0 != nudge {
+			return (0.0, motion, normal, touched);
+		}
+
+		let nudged = if horizontal {
+			Aabb { y: bounds.y + nudge, ..bounds }
+		} else {
+			Aabb { x: bounds.x + nudge, ..bounds }
 		};
+		let (nudged_motion, nudged_normal, nudged_touched) = self.sweep_axis(nudged, amount, horizontal, solids);
+		if nudged_motion.abs() > motion.abs() {
+			(nudge, nudged_motion, nudged_normal, nudged_touched)
+		} else {
+			(0.0, motion, normal, touched)
+		}
 	}
 
-	pub fn remove_entity(&mut self, solid: char, tile_x :usize, tile_y :usize) {
+	// Sweeps the box along one axis. Returns the allowed motion, the contact normal on that axis
+	// (0 if nothing was touched) and the tiles touched.
+	fn sweep_axis(&self, bounds: Aabb, amount: f32, horizontal: bool, solids: &[char]) -> (f32, f32, Vec<(usize, usize)>) {
+		if amount == 0.0 {
+			return (0.0, 0.0, Vec::new());
+		}
+
+		// work in (along, across) coordinates so both axes share the code.
+		let (start, size, across_start, across_size) = if horizontal {
+			(bounds.x, bounds.w, bounds.y, bounds.h)
+		} else {
+			(bounds.y, bounds.h, bounds.x, bounds.w)
+		};
 
+		// tiles across the motion that the box overlaps, ignoring edges it only touches.
+		let first_across = ((across_start + Self::SKIN) / Self::TILE_SIZE).floor() as i64;
+		let last_across = ((across_start + across_size - Self::SKIN) / Self::TILE_SIZE).floor() as i64;
+
+		// tiles along the motion, nearest first. A box overlapping a tile by less than the skin still
+		// counts it as ahead, which pulls it back onto the tile edge.
+		let lines: Vec<i64> = if amount > 0.0 {
+			let lead = start + size;
+			let first = ((lead - Self::SKIN) / Self::TILE_SIZE).ceil() as i64;
+			let last = ((lead + amount) / Self::TILE_SIZE).floor() as i64;
+			(first..=last).collect()
+		} else {
+			let lead = start;
+			let first = ((lead + Self::SKIN) / Self::TILE_SIZE).floor() as i64 - 1;
+			let last = ((lead + amount) / Self::TILE_SIZE).ceil() as i64 - 1;
+			(last..=first).rev().collect()
+		};
+
+		for along in lines {
+			let touched: Vec<(usize, usize)> = (first_across..=last_across)
+				.map(|across| if horizontal { (along, across) } else { (across, along) })
+				.filter(|&(tile_x, tile_y)| self.tile_is_any(tile_x, tile_y, solids))
+				.map(|(tile_x, tile_y)| (tile_x as usize, tile_y as usize))
+				.collect();
+
+			if !touched.is_empty() {
+				let allowed = if amount > 0.0 {
+					along as f32 * Self::TILE_SIZE - (start + size)
+				} else {
+					(along + 1) as f32 * Self::TILE_SIZE - start
+				};
+				return (allowed, -amount.signum(), touched);
+			}
+		}
+
+		(amount, 0.0, Vec::new())
+	}
+
+	pub fn tile_set(&self) -> &TileSet {
+		&self.tile_set
+	}
+
+	// tiles that block movement on this map.
+	pub fn solids(&self) -> &[char] {
+		self.tile_set.solids()
+	}
+
+	// replace a tile, keeping the autotiles around it up to date.
+	pub fn set_tile(&mut self, tile_x: usize, tile_y: usize, tile: char) {
 		if let Some(row) = self.tiles.get_mut(tile_y) {
-			if let Some(tile) = row.get_mut(tile_x) {
-				if *tile == solid {
-					*tile = ' ';
-				}
+			if let Some(old) = row.get_mut(tile_x) {
+				*old = tile;
+				self.update_autotiles(tile_y.saturating_sub(1), tile_x.saturating_sub(1), tile_y + 2, tile_x + 2);
 			}
 		}
 	}
 
 	pub fn get_mapping(&self, tile : char) -> Option<&String> {
-		return self.mappings.get(&tile);
+		self.mappings.iter().find(|(symbol, _)| *symbol == tile).map(|(_, target)| target)
 	}
 
-	pub fn find_portal_coordinates(&self, portal_char : char) -> Option<(f32, f32)> {
-		for (row_index, row) in self.tiles.iter().enumerate() {
-			for (col_index, &tile) in row.iter().enumerate() {
-				if tile == portal_char {
-					return Some((col_index as f32 * Self::TILE_SIZE, row_index as f32 * Self::TILE_SIZE));
-				}
+	// all portal mappings in file order.
+	pub fn mappings(&self) -> &[(char, String)] {
+		&self.mappings
+	}
+
+	// point a portal at another map, or remove its mapping with None.
+	pub fn set_mapping(&mut self, tile: char, target: Option<String>) {
+		let index = self.mappings.iter().position(|(symbol, _)| *symbol == tile);
+		match (index, target) {
+			(Some(index), Some(target)) => self.mappings[index].1 = target,
+			(Some(index), None) => {
+				self.mappings.remove(index);
 			}
+			(None, Some(target)) => self.mappings.push((tile, target)),
+			(None, None) => {}
 		}
+	}
+}
 
-		return None;
+// Writes the map in the format `Map::parse_map` reads: the rows of tiles, then a blank line and
+// a "[x] target" line per portal mapping.
+impl fmt::Display for Map {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		for row in &self.tiles {
+			writeln!(f, "{}", row.iter().collect::<String>())?;
+		}
+		if !self.mappings.is_empty() || self.music.is_some() {
+			writeln!(f)?;
+			for (symbol, target) in &self.mappings {
+				writeln!(f, "[{}] {}", symbol, target)?;
+			}
+			if let Some(music) = &self.music {
+				writeln!(f, "music {}", music)?;
+			}
+		}
+		Ok(())
 	}
 }
 
@@ -341,16 +604,257 @@ impl Map {
 mod tests {
 	use super::*;
 
+	fn tiles() -> Rc<TileSet> {
+		Rc::new(TileSet::from_file(TileSet::PATH).unwrap())
+	}
+
+	fn load(name: &str) -> Map {
+		Map::from_file(&format!("maps/{}", name), tiles()).unwrap()
+	}
+
 	#[test]
 	fn test_raycast_hits_wall() {
-		let map = Map::from_file("maps/map1.txt");
+		let map = load("map1.txt");
 
 		let start = (32.0, 32.0);
 		let dir = (1.0, 0.0);
-		let distance = 10.0;
+		let distance = 200.0;
 
 		let result = map.raycast(start, dir, distance);
 
-		assert_eq!(result, Some(64.0, _, _));
+		assert_eq!(result, Some(96.0));
+	}
+
+	#[test]
+	fn test_raycast_reports_tile_and_normal() {
+		let map = load("map1.txt");
+
+		// left into the outer wall.
+		let hit = map.raycast_any((64.0, 100.0), (-1.0, 0.0), 1000.0, &['x']);
+		assert_eq!(hit, Some(RayHit { distance: 32.0, tile_x: 0, tile_y: 3, normal: (1.0, 0.0) }));
+
+		// down onto the ledge below spawn.
+		let hit = map.raycast_any((48.0, 40.0), (0.0, 1.0), 1000.0, &['x']);
+		assert_eq!(hit, Some(RayHit { distance: 376.0, tile_x: 1, tile_y: 13, normal: (0.0, -1.0) }));
+	}
+
+	#[test]
+	fn test_raycast_diagonal_corner() {
+		let map = load("map1.txt");
+
+		// through the exact corner of tile (1, 1), the ceiling is hit right there.
+		let hit = map.raycast_any((48.0, 48.0), (1.0, -1.0), 1000.0, &['x']).unwrap();
+		assert!((hit.distance - 16.0 * 2.0_f32.sqrt()).abs() < 0.001);
+		assert_eq!((hit.tile_x, hit.tile_y), (1, 0));
+		assert_eq!(hit.normal, (0.0, 1.0));
+	}
+
+	#[test]
+	fn test_raycast_distance_and_start() {
+		let map = load("map1.txt");
+
+		// too short to reach the wall.
+		assert_eq!(map.raycast((32.0, 32.0), (1.0, 0.0), 95.0), None);
+
+		// standing exactly on the floor.
+		assert_eq!(map.raycast((48.0, 13.0 * Map::TILE_SIZE), (0.0, 1.0), 100.0), Some(0.0));
+
+		// touching the ceiling from below.
+		assert_eq!(map.raycast((48.0, Map::TILE_SIZE), (0.0, -1.0), 100.0), Some(0.0));
+	}
+
+	#[test]
+	fn test_raycast_solid_set() {
+		let map = load("map1.txt");
+
+		// the coin on row 5 is behind open space, walls are not in the set.
+		let hit = map.raycast_any((48.0, 176.0), (1.0, 0.0), 1000.0, &['c', 'g']).unwrap();
+		assert_eq!((hit.tile_x, hit.tile_y, hit.distance), (17, 5, 496.0));
+	}
+
+	#[test]
+	fn test_sweep_lands_on_floor() {
+		let map = load("map1.txt");
+
+		// falling next to the outer wall must not catch on it.
+		let bounds = Aabb { x: 32.0, y: 40.0, w: 32.0, h: 32.0 };
+		let sweep = map.sweep(bounds, (0.0, 1000.0), map.solids());
+		assert_eq!(sweep.motion, (0.0, 344.0));
+		assert_eq!(sweep.normal, (0.0, -1.0));
+		assert_eq!(sweep.touched, vec![(1, 13)]);
+
+		// resting on the floor, can't go down but can walk.
+		let bounds = Aabb { x: 32.0, y: 384.0, w: 32.0, h: 32.0 };
+		let sweep = map.sweep(bounds, (3.0, 5.0), map.solids());
+		assert_eq!(sweep.motion, (3.0, 0.0));
+		assert_eq!(sweep.normal, (0.0, -1.0));
+	}
+
+	#[test]
+	fn test_sweep_diagonal() {
+		let map = load("map1.txt");
+
+		// blocked by the wall at column 4 first, then falls down to row 13.
+		let bounds = Aabb { x: 32.0, y: 32.0, w: 32.0, h: 32.0 };
+		let sweep = map.sweep(bounds, (1000.0, 1000.0), map.solids());
+		assert_eq!(sweep.motion, (64.0, 352.0));
+		assert_eq!(sweep.normal, (-1.0, -1.0));
+		assert_eq!(sweep.touched, vec![(4, 1), (3, 13)]);
+
+		// up and left into the corner below the ceiling.
+		let bounds = Aabb { x: 64.0, y: 96.0, w: 32.0, h: 32.0 };
+		let sweep = map.sweep(bounds, (-100.0, -100.0), map.solids());
+		assert_eq!(sweep.motion, (-32.0, -64.0));
+		assert_eq!(sweep.normal, (1.0, 1.0));
+	}
+
+	#[test]
+	fn test_sweep_pulls_back_rounding() {
+		let map = load("map1.txt");
+
+		// a hair inside the floor is pushed back onto it.
+		let bounds = Aabb { x: 32.0, y: 384.001, w: 32.0, h: 32.0 };
+		let sweep = map.sweep(bounds, (0.0, 2.0), map.solids());
+		assert!((sweep.motion.1 + 0.001).abs() < 0.0001);
+		assert_eq!(sweep.normal, (0.0, -1.0));
+	}
+
+	#[test]
+	fn test_sweep_lines_up_with_gap() {
+		let map = Map::parse_map("xxxxxx\nx    x\nxx xxx\nx    x\nxxxxxx\n", tiles()).unwrap();
+
+		// half a pixel off the gap in the floor still drops through it.
+		let bounds = Aabb { x: 64.5, y: 32.0, w: 32.0, h: 32.0 };
+		let sweep = map.sweep(bounds, (0.0, 100.0), map.solids());
+		assert_eq!(sweep.motion, (-0.5, 64.0));
+		assert_eq!(sweep.normal, (0.0, -1.0));
+
+		// more than `NUDGE` off it lands next to it.
+		let bounds = Aabb { x: 66.0, y: 32.0, w: 32.0, h: 32.0 };
+		let sweep = map.sweep(bounds, (0.0, 100.0), map.solids());
+		assert_eq!(sweep.motion, (0.0, 0.0));
+	}
+
+	#[test]
+	fn test_is_free() {
+		let map = Map::parse_map("xxxx\nx  x\nx  x\nxxxx\n", tiles()).unwrap();
+
+		// resting against the walls and floor is fine, a hair into them isn't.
+		assert!(map.is_free(Aabb { x: 32.0, y: 64.0, w: 64.0, h: 32.0 }, map.solids()));
+		assert!(!map.is_free(Aabb { x: 31.5, y: 64.0, w: 32.0, h: 32.0 }, map.solids()));
+		assert!(!map.is_free(Aabb { x: 32.0, y: 64.5, w: 32.0, h: 32.0 }, map.solids()));
+
+		// nor is leaving the map.
+		let open = Map::parse_map("..\n..\n", tiles()).unwrap();
+		assert!(open.is_free(Aabb { x: 0.0, y: 0.0, w: 64.0, h: 64.0 }, open.solids()));
+		assert!(!open.is_free(Aabb { x: 1.0, y: 0.0, w: 64.0, h: 64.0 }, open.solids()));
+	}
+
+	#[test]
+	fn test_find_path() {
+		let map = Map::parse_map("xxxxxx\nx  x x\nx xx x\nx    x\nxxxxxx\n", tiles()).unwrap();
+
+		// around the wall in the middle.
+		let path = map.find_path((1, 1), (4, 1), 20).unwrap();
+		assert_eq!(path, vec![(1, 2), (1, 3), (2, 3), (3, 3), (4, 3), (4, 2), (4, 1)]);
+		assert_eq!(map.find_path((1, 1), (4, 1), 6), None);
+		assert_eq!(map.find_path((1, 1), (1, 1), 0), Some(Vec::new()));
+	}
+
+	#[test]
+	fn test_autotile_cache() {
+		let mut map = Map::parse_map("xxxxx\nx   x\nx x x\nx xcx\nxxxxx\n", tiles()).unwrap();
+
+		// the top of a pillar standing on the floor, and the pillar below it.
+		assert_eq!(map.autotile(2, 2).unwrap().cell, (4, 0));
+		assert_eq!(map.autotile(3, 2).unwrap().cell, (4, 5));
+		// the outer wall continues outside the map, so its corner is an inner corner open to the room.
+		assert_eq!(map.autotile(0, 0), Some(TileSprite { cell: (11, 8), rotation: 90.0 }));
+		assert_eq!(map.autotile(0, 4), Some(TileSprite { cell: (11, 8), rotation: 180.0 }));
+		assert_eq!(map.autotile(3, 3), None);
+
+		// walling in the room's corner updates its neighbourhood.
+		map.set_tile(1, 1, 'x');
+		assert_eq!(map.tiles[1][1], 'x');
+		assert_eq!(map.autotile(0, 0), Some(TileSprite { cell: (11, 1), rotation: 180.0 }));
+	}
+
+	#[test]
+	fn test_all_maps_parse() {
+		for name in ["map1.txt", "map2.txt", "map3.txt", "map4.txt"] {
+			let map = load(name);
+			assert_eq!(map.width, 40, "{}", name);
+			assert_eq!(map.tiles.len(), map.height, "{}", name);
+		}
+	}
+
+	#[test]
+	fn test_parse_mappings() {
+		let map = Map::parse_map("xxx\nxpx\nxxx\n\n[p] map2.txt\n", tiles()).unwrap();
+		assert_eq!(map.get_mapping('p'), Some(&String::from("map2.txt")));
+		assert_eq!(map.music, None);
+	}
+
+	#[test]
+	fn test_parse_music() {
+		let mut map = Map::parse_map("xxx\nxpx\nxxx\n\n[p] map2.txt\nmusic  cave.ogg \n", tiles()).unwrap();
+		assert_eq!(map.music.as_deref(), Some("cave.ogg"));
+		assert_eq!(map.to_string(), "xxx\nxpx\nxxx\n\n[p] map2.txt\nmusic cave.ogg\n");
+
+		map.music = None;
+		assert_eq!(map.to_string(), "xxx\nxpx\nxxx\n\n[p] map2.txt\n");
+		map.set_mapping('p', None);
+		map.music = Some(String::from("theme.wav"));
+		assert_eq!(map.to_string(), "xxx\nxpx\nxxx\n\nmusic theme.wav\n");
+	}
+
+	#[test]
+	fn test_save_round_trip() {
+		// saving writes the files back unchanged, mappings keep their order.
+		for name in ["map1.txt", "map2.txt", "map3.txt", "map4.txt"] {
+			let content = std::fs::read_to_string(format!("maps/{}", name)).unwrap();
+			assert_eq!(Map::parse_map(&content, tiles()).unwrap().to_string(), content, "{}", name);
+		}
+
+		let mut map = Map::parse_map("xxx\nxpx\nxxx\n\n[p] map2.txt\n[q] map3.txt\n", tiles()).unwrap();
+		map.set_tile(1, 1, 'c');
+		map.set_mapping('p', None);
+		map.set_mapping('q', Some(String::from("win")));
+		map.set_mapping('s', Some(String::from("map4.txt")));
+		assert_eq!(map.to_string(), "xxx\nxcx\nxxx\n\n[q] win\n[s] map4.txt\n");
+
+		map.set_mapping('q', None);
+		map.set_mapping('s', None);
+		assert_eq!(map.to_string(), "xxx\nxcx\nxxx\n");
+	}
+
+	#[test]
+	fn test_ragged_row() {
+		let result = Map::parse_map("xxxx\nx x\nxxxx\n", tiles());
+		assert_eq!(result.err(), Some(MapError::RaggedRow { line: 2, column: 4, expected: 4, found: 3 }));
+	}
+
+	#[test]
+	fn test_unknown_tile() {
+		let result = Map::parse_map("xxxx\nx zx\nxxxx\n", tiles());
+		assert_eq!(result.err(), Some(MapError::UnknownTile { tile: 'z', line: 2, column: 3 }));
+	}
+
+	#[test]
+	fn test_malformed_metadata() {
+		let result = Map::parse_map("xpx\n\n[p] map2.txt\n[q map3.txt\n", tiles());
+		assert_eq!(result.err(), Some(MapError::MalformedMetadata { line: 4, column: 3, text: String::from("[q map3.txt") }));
+
+		let result = Map::parse_map("xpx\n\n[p]\n", tiles());
+		assert_eq!(result.err(), Some(MapError::MalformedMetadata { line: 3, column: 4, text: String::from("[p]") }));
+
+		let result = Map::parse_map("xpx\n\np map2.txt\n", tiles());
+		assert_eq!(result.err(), Some(MapError::MalformedMetadata { line: 3, column: 1, text: String::from("p map2.txt") }));
+	}
+
+	#[test]
+	fn test_portal_without_mapping() {
+		let result = Map::parse_map("xxx\nxpx\nxsx\n\n[p] map2.txt\n", tiles());
+		assert_eq!(result.err(), Some(MapError::PortalWithoutMapping { portal: 's', line: 3, column: 2 }));
 	}
 }
@@ -0,0 +1,126 @@
+use crate::autotile::TileSprite;
+use crate::config::ConfigError;
+use crate::map::Map;
+use crate::tiles::{TileDef, TileSet};
+
+use macroquad::prelude::*;
+
+// Draws a `Map` using the tile sheet. Kept apart from `Map` so maps can be loaded and
+// simulated without a graphics context.
+pub struct MapRenderer {
+	sprite_bg1: Texture2D,
+}
+
+impl MapRenderer {
+
+	pub const SPRITE_PATH: &'static str = "sprites/bg1.png";
+
+	pub async fn load() -> Result<Self, ConfigError> {
+		let bytes = load_file(Self::SPRITE_PATH).await.map_err(|err| ConfigError::MissingFile {
+			path: Self::SPRITE_PATH.to_string(),
+			reason: err.to_string(),
+		})?;
+		// decoded here as macroquad panics on an image it can't read, e.g. one caught half saved.
+		let image = image::load_from_memory(&bytes).map_err(|err| ConfigError::Invalid {
+			path: Self::SPRITE_PATH.to_string(),
+			reason: err.to_string(),
+		})?.to_rgba8();
+		let sprite_bg1 = Texture2D::from_rgba8(image.width() as u16, image.height() as u16, &image);
+		sprite_bg1.set_filter(FilterMode::Nearest);
+
+		Ok(MapRenderer {
+			sprite_bg1,
+		})
+	}
+
+	// load the tile sheet again after it changed on disk, the old one is kept if that fails.
+	pub async fn reload(&mut self) -> Result<(), ConfigError> {
+		let old = self.sprite_bg1;
+		*self = Self::load().await?;
+		old.delete();
+		Ok(())
+	}
+
+	// A renderer without a tile sheet, tiles are drawn as blank squares.
+	pub fn empty() -> Self {
+		MapRenderer {
+			sprite_bg1: Texture2D::empty(),
+		}
+	}
+
+	// The tile sheet, also used to draw the player.
+	pub fn sprite(&self) -> Texture2D {
+		self.sprite_bg1
+	}
+
+	// draw the tiles of the map that are inside `view`, animated tiles at the current time.
+	pub fn draw(&self, map: &Map, view: Rect) {
+		let time = get_time() as f32;
+		let first_col = (view.x / Map::TILE_SIZE).floor().max(0.0) as usize;
+		let first_row = (view.y / Map::TILE_SIZE).floor().max(0.0) as usize;
+		let last_col = (((view.x + view.w) / Map::TILE_SIZE).ceil().max(0.0) as usize).min(map.width);
+		let last_row = (((view.y + view.h) / Map::TILE_SIZE).ceil().max(0.0) as usize).min(map.height);
+
+		for row_index in first_row..last_row {
+			for col_index in first_col..last_col {
+				let tile = map.tiles[row_index][col_index];
+				let x = col_index as f32 * Map::TILE_SIZE;
+				let y = row_index as f32 * Map::TILE_SIZE;
+				self.draw_def(map.tile_set().get(tile), map.autotile(row_index, col_index), x, y, time);
+			}
+		}
+	}
+
+	// draw a single tile on its own, e.g. in the editor palette. Autotiled tiles look like a
+	// tile without neighbours.
+	pub fn draw_tile(&self, tile_set: &TileSet, tile: char, x: f32, y: f32) {
+		let autotile = tile_set.autotile(tile).and_then(|autotile| autotile.pick(0));
+		self.draw_def(tile_set.get(tile), autotile, x, y, get_time() as f32);
+	}
+
+	fn draw_def(&self, def: Option<&TileDef>, autotile: Option<TileSprite>, x: f32, y: f32, time: f32) {
+		match def {
+			Some(TileDef { autotile: Some(_), .. }) => {
+				match autotile {
+					Some(sprite) => {
+						self.texture_rot(self.sprite_bg1, x, y, sprite.cell.0, sprite.cell.1, sprite.rotation);
+					}
+					None => {
+						draw_rectangle(x, y, Map::TILE_SIZE, Map::TILE_SIZE, PINK);
+					}
+				}
+			}
+			Some(def) => match (def.cell(time), def.color) {
+				(Some((pos_x, pos_y)), _) => {
+					self.texture_rot(self.sprite_bg1, x, y, pos_x, pos_y, def.rotation);
+				}
+				(None, Some((r, g, b))) => {
+					draw_rectangle(x, y, Map::TILE_SIZE, Map::TILE_SIZE, Color::from_rgba(r, g, b, 255));
+				}
+				(None, None) => {
+					draw_rectangle(x, y, Map::TILE_SIZE, Map::TILE_SIZE, PINK);
+				}
+			},
+			None => {
+				draw_rectangle(x, y, Map::TILE_SIZE, Map::TILE_SIZE, PINK);
+			}
+		}
+	}
+
+	fn texture_rot(&self, image: Texture2D, x: f32, y: f32, pos_x: i32, pos_y: i32, rotation_deg: f32) {
+		draw_texture_ex(
+			image,
+			x,
+			y,
+			WHITE,
+			DrawTextureParams {
+				dest_size: Some(vec2(Map::TILE_SIZE, Map::TILE_SIZE)),
+				source: Some(Rect::new(pos_x as f32 * Map::TILE_SIZE, pos_y as f32 * Map::TILE_SIZE, Map::TILE_SIZE, Map::TILE_SIZE)),
+				rotation: rotation_deg.to_radians(),
+				pivot: None,
+				..Default::default()
+			},
+		);
+	}
+
+}
@@ -0,0 +1,133 @@
+use crate::gamepad::Gamepads;
+
+use gilrs::Button;
+use macroquad::prelude::*;
+
+// One step through a menu.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MenuInput {
+	Up,
+	Down,
+	Select,
+	Back,
+}
+
+impl MenuInput {
+
+	// the steps pressed this frame on the keyboard or a gamepad.
+	pub fn read(gamepads: &Gamepads) -> Vec<MenuInput> {
+		let mut inputs = Vec::new();
+		for (keys, buttons, input) in [
+			(&[KeyCode::Up, KeyCode::W][..], &[Button::DPadUp][..], MenuInput::Up),
+			(&[KeyCode::Down, KeyCode::S][..], &[Button::DPadDown][..], MenuInput::Down),
+			(&[KeyCode::Enter, KeyCode::KpEnter, KeyCode::Space][..], &[Button::South][..], MenuInput::Select),
+			(&[KeyCode::Escape, KeyCode::Backspace][..], &[Button::East, Button::Select][..], MenuInput::Back),
+		] {
+			if keys.iter().any(|&key| is_key_pressed(key)) || buttons.iter().any(|&button| gamepads.is_pressed(button)) {
+				inputs.push(input);
+			}
+		}
+		inputs
+	}
+}
+
+// What a menu input did.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MenuChoice {
+	// the item at this index was picked.
+	Picked(usize),
+	Back,
+}
+
+// A list of items to pick from, the selection wraps around at both ends.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Menu {
+	pub title: String,
+	items: Vec<String>,
+	pub selected: usize,
+}
+
+impl Menu {
+
+	const TITLE_SIZE: f32 = 48.0;
+	const ITEM_SIZE: f32 = 32.0;
+	const ITEM_SPACING: f32 = 44.0;
+
+	pub fn new(title: &str, items: Vec<String>) -> Self {
+		Menu {
+			title: title.to_string(),
+			items,
+			selected: 0,
+		}
+	}
+
+	pub fn item(&self, index: usize) -> Option<&str> {
+		self.items.get(index).map(String::as_str)
+	}
+
+	pub fn navigate(&mut self, input: MenuInput) -> Option<MenuChoice> {
+		match input {
+			MenuInput::Up if !self.items.is_empty() => {
+				self.selected = (self.selected + self.items.len() - 1) % self.items.len();
+				None
+			}
+			MenuInput::Down if !self.items.is_empty() => {
+				self.selected = (self.selected + 1) % self.items.len();
+				None
+			}
+			MenuInput::Select if self.selected < self.items.len() => Some(MenuChoice::Picked(self.selected)),
+			MenuInput::Back => Some(MenuChoice::Back),
+			_ => None,
+		}
+	}
+
+	// read the keyboard and gamepads for one frame.
+	pub fn update(&mut self, gamepads: &Gamepads) -> Option<MenuChoice> {
+		MenuInput::read(gamepads).into_iter().find_map(|input| self.navigate(input))
+	}
+
+	// draw the title and items centred on the screen, in screen coordinates.
+	pub fn draw(&self) {
+		let height = Self::TITLE_SIZE + self.items.len() as f32 * Self::ITEM_SPACING;
+		let mut y = (screen_height() - height) / 2.0 + Self::TITLE_SIZE;
+		draw_centred(&self.title, y, Self::TITLE_SIZE, WHITE);
+		y += Self::ITEM_SPACING;
+
+		for (index, item) in self.items.iter().enumerate() {
+			y += Self::ITEM_SPACING;
+			if index == self.selected {
+				draw_centred(&format!("> {} <", item), y, Self::ITEM_SIZE, YELLOW);
+			} else {
+				draw_centred(item, y, Self::ITEM_SIZE, WHITE);
+			}
+		}
+	}
+}
+
+fn draw_centred(text: &str, y: f32, size: f32, color: Color) {
+	let width = measure_text(text, None, size as u16, 1.0).width;
+	draw_text(text, (screen_width() - width) / 2.0, y, size, color);
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_navigate_wraps() {
+		let mut menu = Menu::new("Paused", vec![String::from("Resume"), String::from("Restart level"), String::from("Quit to menu")]);
+		assert_eq!(menu.navigate(MenuInput::Up), None);
+		assert_eq!(menu.selected, 2);
+		assert_eq!(menu.navigate(MenuInput::Down), None);
+		assert_eq!(menu.navigate(MenuInput::Down), None);
+		assert_eq!(menu.navigate(MenuInput::Select), Some(MenuChoice::Picked(1)));
+		assert_eq!(menu.item(1), Some("Restart level"));
+		assert_eq!(menu.navigate(MenuInput::Back), Some(MenuChoice::Back));
+
+		// an empty menu can only be left.
+		let mut empty = Menu::new("Select level", Vec::new());
+		assert_eq!(empty.navigate(MenuInput::Down), None);
+		assert_eq!(empty.navigate(MenuInput::Select), None);
+		assert_eq!(empty.navigate(MenuInput::Back), Some(MenuChoice::Back));
+	}
+}
@@ -0,0 +1,394 @@
+use crate::config::ConfigError;
+use crate::entity::Entities;
+use crate::map::{Aabb, Map};
+use crate::player::{Player, Pose};
+use crate::tiles::{TileSet, Touch};
+use crate::world::Event;
+
+use macroquad::prelude::*;
+use serde::Deserialize;
+
+// What sets off an emitter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Trigger {
+	// a coin was picked up.
+	Collect,
+	// a gravity pickup was taken.
+	Gravity,
+	// an enemy was stomped.
+	Stomp,
+	// the player died, where it died.
+	Death,
+	// the player landed on a floor.
+	Land,
+	// every tick the player slides without gravity, a trail behind it.
+	Slide,
+	// a checkpoint was reached.
+	Checkpoint,
+}
+
+impl Trigger {
+
+	/*
+	 * The triggers of one tick with the area to emit in, from the events its commands raised and
+	 * how the player changed during it. A tick that put the player back at a spawn only counts
+	 * its events, like `Effect::of_tick`.
+	 * */
+	pub fn of_tick(before: &Player, after: &Player, events: &[Event], tile_set: &TileSet) -> Vec<(Trigger, Aabb)> {
+		let mut triggers = Vec::new();
+		let mut respawned = false;
+		for event in events {
+			match event {
+				Event::Removed { tile, x, y } => {
+					let area = Aabb { x: *x, y: *y, w: Map::TILE_SIZE, h: Map::TILE_SIZE };
+					let trigger = match tile_set.get(*tile) {
+						Some(def) if def.enemy.is_some() => Some(Trigger::Stomp),
+						Some(def) if def.touch == Some(Touch::Collect) => Some(Trigger::Collect),
+						Some(def) if def.touch == Some(Touch::Gravity) => Some(Trigger::Gravity),
+						_ => None,
+					};
+					triggers.extend(trigger.map(|trigger| (trigger, area)));
+				}
+				Event::Checkpoint { x, y } => {
+					triggers.push((Trigger::Checkpoint, Aabb { x: *x, y: *y, w: Map::TILE_SIZE, h: Map::TILE_SIZE }));
+				}
+				Event::Died { .. } => {
+					triggers.push((Trigger::Death, before.bounds()));
+					respawned = true;
+				}
+				Event::Respawned | Event::EnteredPortal { .. } | Event::Won { .. } | Event::Reset => respawned = true,
+				_ => {}
+			}
+		}
+		if respawned {
+			return triggers;
+		}
+
+		if after.has_gravity() && before.vy() > 0.0 && after.vy() == 0.0 && after.pose() != Pose::Jump {
+			triggers.push((Trigger::Land, Aabb { x: after.x, y: after.bottom(), w: Player::WIDTH, h: 0.0 }));
+		}
+		if after.pose() == Pose::Slide {
+			triggers.push((Trigger::Slide, after.bounds()));
+		}
+		triggers
+	}
+}
+
+fn all_directions() -> f32 {
+	180.0
+}
+
+/*
+ * A kind of particle and when it is emitted, defined in particles.toml. Emitters set off by
+ * `on` burst `count` particles at once, slide trails and emitters sitting on the entities spawned
+ * from `tile` emit `rate` particles per second instead.
+ * */
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EmitterDef {
+	pub name: String,
+	#[serde(default)]
+	pub on: Option<Trigger>,
+	#[serde(default)]
+	pub tile: Option<char>,
+	#[serde(default)]
+	pub count: u32,
+	#[serde(default)]
+	pub rate: f32,
+	// seconds a particle lives, [shortest, longest].
+	pub lifetime: (f32, f32),
+	// pixels per second, [slowest, fastest].
+	pub speed: (f32, f32),
+	// degrees, 0 is right and 90 down.
+	#[serde(default)]
+	pub direction: f32,
+	// degrees to either side of the direction.
+	#[serde(default = "all_directions")]
+	pub spread: f32,
+	// pixels per second squared pulling particles down, negative lets them rise.
+	#[serde(default)]
+	pub gravity: f32,
+	// pixels at the start and the end of a particle's life.
+	pub size: (f32, f32),
+	pub color: (u8, u8, u8),
+	// the colour particles fade to, the start colour if not set.
+	#[serde(default)]
+	pub end_color: Option<(u8, u8, u8)>,
+}
+
+impl EmitterDef {
+
+	// true if the emitter emits over time rather than in bursts.
+	pub fn is_continuous(&self) -> bool {
+		self.tile.is_some() || self.on == Some(Trigger::Slide)
+	}
+
+	pub fn check(&self) -> Result<(), String> {
+		if self.on.is_some() == self.tile.is_some() {
+			return Err(format!("emitter '{}' needs either 'on' or 'tile'", self.name));
+		}
+		if self.is_continuous() && self.rate <= 0.0 {
+			return Err(format!("emitter '{}' emits over time and needs a positive 'rate'", self.name));
+		}
+		if !self.is_continuous() && self.count == 0 {
+			return Err(format!("emitter '{}' bursts and needs a 'count'", self.name));
+		}
+		if self.lifetime.0 <= 0.0 || self.lifetime.1 < self.lifetime.0 {
+			return Err(format!("emitter '{}' has lifetime {:?}, expected [shortest, longest] above 0", self.name, self.lifetime));
+		}
+		Ok(())
+	}
+}
+
+#[derive(Deserialize)]
+struct ParticleFile {
+	#[serde(default)]
+	emitter: Vec<EmitterDef>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Particle {
+	x: f32,
+	y: f32,
+	vx: f32,
+	vy: f32,
+	age: f32,
+	lifetime: f32,
+	// index of the emitter it came from, for its looks.
+	emitter: usize,
+}
+
+/*
+ * Short-lived coloured squares drawn above the map. They are kept in a pool allocated once,
+ * the live ones at the front, so emitting never allocates. When the pool is full new particles
+ * are dropped. Particles only decorate, they don't touch the simulation or its random numbers.
+ * */
+pub struct Particles {
+	emitters: Vec<EmitterDef>,
+	pool: Vec<Particle>,
+	alive: usize,
+	// state of the xorshift generator spreading the particles.
+	seed: u64,
+}
+
+impl Default for Particles {
+	fn default() -> Self {
+		Particles {
+			emitters: Vec::new(),
+			pool: vec![Particle::default(); Self::CAPACITY],
+			alive: 0,
+			seed: 0x2545_f491_4f6c_dd1d,
+		}
+	}
+}
+
+impl Particles {
+
+	pub const PATH: &'static str = "particles.toml";
+
+	// most particles alive at once.
+	pub const CAPACITY: usize = 2048;
+
+	pub fn load(path: &str) -> Result<Self, ConfigError> {
+		let content = std::fs::read_to_string(path).map_err(|err| ConfigError::MissingFile {
+			path: path.to_string(),
+			reason: err.to_string(),
+		})?;
+
+		Self::parse(&content).map_err(|reason| ConfigError::Invalid {
+			path: path.to_string(),
+			reason,
+		})
+	}
+
+	pub fn parse(content: &str) -> Result<Self, String> {
+		let file: ParticleFile = toml::from_str(content).map_err(|err| err.to_string())?;
+		for emitter in &file.emitter {
+			emitter.check()?;
+		}
+		Ok(Particles { emitters: file.emitter, ..Self::default() })
+	}
+
+	pub fn len(&self) -> usize {
+		self.alive
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.alive == 0
+	}
+
+	pub fn clear(&mut self) {
+		self.alive = 0;
+	}
+
+	// set off the emitters of `trigger` in `area`, continuous ones for `dt` seconds.
+	pub fn emit(&mut self, trigger: Trigger, area: Aabb, dt: f32) {
+		for index in 0..self.emitters.len() {
+			if self.emitters[index].on == Some(trigger) {
+				self.emit_from(index, area, dt);
+			}
+		}
+	}
+
+	// move the particles on by `dt` seconds and let emitters on entities inside `view` emit.
+	pub fn update(&mut self, dt: f32, entities: &Entities, view: Rect) {
+		for index in 0..self.emitters.len() {
+			let Some(tile) = self.emitters[index].tile else {
+				continue;
+			};
+			for (_, entity) in entities.iter().filter(|(_, entity)| entity.tile() == tile) {
+				let bounds = entity.bounds();
+				if view.overlaps(&Rect::new(bounds.x, bounds.y, bounds.w, bounds.h)) {
+					self.emit_from(index, bounds, dt);
+				}
+			}
+		}
+
+		let mut index = 0;
+		while index < self.alive {
+			let particle = &mut self.pool[index];
+			particle.age += dt;
+			if particle.age >= particle.lifetime {
+				// the last live particle takes the place of the dead one.
+				self.alive -= 1;
+				self.pool.swap(index, self.alive);
+				continue;
+			}
+			particle.vy += self.emitters[particle.emitter].gravity * dt;
+			particle.x += particle.vx * dt;
+			particle.y += particle.vy * dt;
+			index += 1;
+		}
+	}
+
+	// draw the particles in map coordinates, shrinking and fading over their life.
+	pub fn draw(&self) {
+		for particle in &self.pool[..self.alive] {
+			let emitter = &self.emitters[particle.emitter];
+			let t = particle.age / particle.lifetime;
+			let size = emitter.size.0 + (emitter.size.1 - emitter.size.0) * t;
+			let (r, g, b) = emitter.color;
+			let (end_r, end_g, end_b) = emitter.end_color.unwrap_or(emitter.color);
+			let mix = |from: u8, to: u8| (from as f32 + (to as f32 - from as f32) * t) / 255.0;
+			let color = Color::new(mix(r, end_r), mix(g, end_g), mix(b, end_b), 1.0 - t);
+			draw_rectangle(particle.x - size / 2.0, particle.y - size / 2.0, size, size, color);
+		}
+	}
+
+	fn emit_from(&mut self, index: usize, area: Aabb, dt: f32) {
+		let emitter = &self.emitters[index];
+		let (lifetime, speed, direction, spread) = (emitter.lifetime, emitter.speed, emitter.direction, emitter.spread);
+		let count = if emitter.is_continuous() {
+			// a fraction of a particle is emitted now and then, on average at the rate.
+			let rate = emitter.rate;
+			(rate * dt + self.random()) as u32
+		} else {
+			emitter.count
+		};
+
+		for _ in 0..count {
+			if self.alive == self.pool.len() {
+				return;
+			}
+			let angle = (direction + spread * (self.random() * 2.0 - 1.0)).to_radians();
+			let speed = speed.0 + (speed.1 - speed.0) * self.random();
+			self.pool[self.alive] = Particle {
+				x: area.x + area.w * self.random(),
+				y: area.y + area.h * self.random(),
+				vx: angle.cos() * speed,
+				vy: angle.sin() * speed,
+				age: 0.0,
+				lifetime: lifetime.0 + (lifetime.1 - lifetime.0) * self.random(),
+				emitter: index,
+			};
+			self.alive += 1;
+		}
+	}
+
+	// a number in [0, 1).
+	fn random(&mut self) -> f32 {
+		self.seed ^= self.seed << 13;
+		self.seed ^= self.seed >> 7;
+		self.seed ^= self.seed << 17;
+		(self.seed >> 40) as f32 / (1u64 << 24) as f32
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const AREA: Aabb = Aabb { x: 64.0, y: 32.0, w: 32.0, h: 32.0 };
+
+	fn view() -> Rect {
+		Rect::new(0.0, 0.0, 1280.0, 640.0)
+	}
+
+	#[test]
+	fn test_load_particles_toml() {
+		let particles = Particles::load(Particles::PATH).unwrap();
+		assert!(particles.emitters.iter().any(|emitter| emitter.on == Some(Trigger::Collect)));
+		assert!(particles.emitters.iter().any(|emitter| emitter.tile == Some('f')));
+	}
+
+	#[test]
+	fn test_invalid_emitters() {
+		let emitter = "[[emitter]]\nname = \"dust\"\nlifetime = [0.2, 0.4]\nspeed = [10.0, 20.0]\nsize = [3.0, 1.0]\ncolor = [200, 200, 200]\n";
+		assert!(Particles::parse(&format!("{}count = 4\n", emitter)).is_err());
+		assert!(Particles::parse(&format!("{}on = \"land\"\n", emitter)).is_err());
+		assert!(Particles::parse(&format!("{}on = \"slide\"\ncount = 4\n", emitter)).is_err());
+		assert!(Particles::parse(&format!("{}on = \"land\"\ntile = \"f\"\ncount = 4\n", emitter)).is_err());
+		assert!(Particles::parse(&format!("{}on = \"land\"\ncount = 4\n", emitter)).is_ok());
+	}
+
+	fn no_entities() -> Entities {
+		Entities::spawn(&mut Map::empty())
+	}
+
+	#[test]
+	fn test_pool() {
+		let mut particles = Particles::parse(
+			"[[emitter]]\nname = \"coin\"\non = \"collect\"\ncount = 1500\nlifetime = [0.5, 1.0]\nspeed = [10.0, 50.0]\nsize = [4.0, 1.0]\ncolor = [255, 220, 0]\n",
+		).unwrap();
+		particles.emit(Trigger::Land, AREA, 0.1);
+		assert!(particles.is_empty());
+
+		// a full pool drops new particles.
+		particles.emit(Trigger::Collect, AREA, 0.1);
+		assert_eq!(particles.len(), 1500);
+		particles.emit(Trigger::Collect, AREA, 0.1);
+		assert_eq!(particles.len(), Particles::CAPACITY);
+
+		particles.update(0.4, &no_entities(), view());
+		assert_eq!(particles.len(), Particles::CAPACITY);
+		particles.update(0.7, &no_entities(), view());
+		assert!(particles.is_empty());
+	}
+
+	#[test]
+	fn test_triggers() {
+		let tile_set = TileSet::from_file(TileSet::PATH).unwrap();
+		let before = Player::new();
+
+		let mut after = before.clone();
+		after.add_coins(1);
+		let coin = Event::Removed { tile: 'c', x: 64.0, y: 32.0 };
+		let stomped = Event::Removed { tile: 'w', x: 96.0, y: 32.0 };
+		assert_eq!(
+			Trigger::of_tick(&before, &after, &[coin, stomped], &tile_set),
+			[(Trigger::Collect, AREA), (Trigger::Stomp, Aabb { x: 96.0, ..AREA })],
+		);
+
+		let mut falling = before.clone();
+		falling.vy = 2.0;
+		assert_eq!(
+			Trigger::of_tick(&falling, &before, &[], &tile_set),
+			[(Trigger::Land, Aabb { x: before.x, y: before.bottom(), w: Player::WIDTH, h: 0.0 })],
+		);
+
+		// dying bursts where the player was, not at the spawn.
+		let died = Event::Died { tile: 'f', tile_x: 3, tile_y: 4 };
+		assert_eq!(Trigger::of_tick(&falling, &before, &[died], &tile_set), [(Trigger::Death, falling.bounds())]);
+	}
+}
@@ -0,0 +1,243 @@
+use crate::config::ConfigError;
+use crate::input::Input;
+
+use std::fmt;
+use std::path::Path;
+
+/*
+ * The input of every tick of a run, starting on `map` with a fresh player. The simulation is
+ * deterministic so feeding the inputs back in plays the run again. Saved as text:
+ *
+ *   xrunner replay 1
+ *   map map1.txt
+ *   seed 1718000000
+ *   inputs
+ *   60:00 12:04 1:05 ...
+ *
+ * where every run of ticks with the same keys is written as `count:bitmask`, the bitmask in hex.
+ * */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay {
+	pub map: String,
+	pub seed: u64,
+	inputs: Vec<Input>,
+}
+
+impl Replay {
+
+	// where the last run is saved when the game exits.
+	pub const LAST_PATH: &'static str = "replays/last.replay";
+
+	const HEADER: &'static str = "xrunner replay 1";
+
+	// runs written per line.
+	const RUNS_PER_LINE: usize = 16;
+
+	pub fn new(map: &str, seed: u64) -> Self {
+		Replay {
+			map: map.to_string(),
+			seed,
+			inputs: Vec::new(),
+		}
+	}
+
+	pub fn record(&mut self, input: Input) {
+		self.inputs.push(input);
+	}
+
+	// the input of a tick, None once the recording is over.
+	pub fn input(&self, tick: usize) -> Option<Input> {
+		self.inputs.get(tick).copied()
+	}
+
+	pub fn len(&self) -> usize {
+		self.inputs.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.inputs.is_empty()
+	}
+
+	pub fn load(path: &str) -> Result<Self, ConfigError> {
+		let content = std::fs::read_to_string(path).map_err(|err| ConfigError::MissingFile {
+			path: path.to_string(),
+			reason: err.to_string(),
+		})?;
+
+		Self::parse(&content).map_err(|reason| ConfigError::Invalid {
+			path: path.to_string(),
+			reason,
+		})
+	}
+
+	pub fn save(&self, path: &str) -> std::io::Result<()> {
+		if let Some(dir) = Path::new(path).parent() {
+			std::fs::create_dir_all(dir)?;
+		}
+		std::fs::write(path, self.to_string())
+	}
+
+	/*
+	 * A replay written by hand, as runs of `ticks*keys` separated by whitespace, e.g.
+	 * "40*right 1*right+jump 60*none". Key names are listed in `Input::NAMES`.
+	 * */
+	pub fn from_script(map: &str, script: &str) -> Result<Self, String> {
+		let mut replay = Replay::new(map, 0);
+		for run in script.split_whitespace() {
+			let Some((count, names)) = run.split_once('*') else {
+				return Err(format!("malformed run '{}', expected 'ticks*keys'", run));
+			};
+			let count = count.parse::<usize>().map_err(|_| format!("malformed run '{}', expected 'ticks*keys'", run))?;
+			let input = Input::from_names(names)?;
+			replay.inputs.extend(std::iter::repeat_n(input, count));
+		}
+		Ok(replay)
+	}
+
+	// the inputs written as a script for `from_script`.
+	pub fn script(&self) -> String {
+		let runs: Vec<String> = self.inputs
+			.chunk_by(|a, b| a == b)
+			.map(|run| format!("{}*{}", run.len(), run[0].names()))
+			.collect();
+		runs.join(" ")
+	}
+
+	pub fn parse(content: &str) -> Result<Self, String> {
+		let mut lines = content.lines().enumerate();
+
+		match lines.next() {
+			Some((_, line)) if line.trim() == Self::HEADER => {}
+			_ => return Err(format!("line 1: expected '{}'", Self::HEADER)),
+		}
+
+		let mut field = |name: &str| -> Result<String, String> {
+			match lines.next() {
+				Some((index, line)) => match line.trim().split_once(' ') {
+					Some((key, value)) if key == name => Ok(value.trim().to_string()),
+					_ => Err(format!("line {}: expected '{} ...'", index + 1, name)),
+				},
+				None => Err(format!("missing '{}'", name)),
+			}
+		};
+		let map = field("map")?;
+		let seed = field("seed")?;
+		let seed = seed.parse::<u64>().map_err(|_| format!("seed '{}' is not a number", seed))?;
+
+		match lines.next() {
+			Some((_, line)) if line.trim() == "inputs" => {}
+			Some((index, _)) => return Err(format!("line {}: expected 'inputs'", index + 1)),
+			None => return Err(String::from("missing 'inputs'")),
+		}
+
+		let mut replay = Replay::new(&map, seed);
+		for (index, line) in lines {
+			for run in line.split_whitespace() {
+				let parsed = run.split_once(':').and_then(|(count, bits)| {
+					Some((count.parse::<usize>().ok()?, u8::from_str_radix(bits, 16).ok()?))
+				});
+				let Some((count, bits)) = parsed else {
+					return Err(format!("line {}: malformed input '{}', expected 'count:bitmask'", index + 1, run));
+				};
+				replay.inputs.extend(std::iter::repeat_n(Input(bits), count));
+			}
+		}
+		Ok(replay)
+	}
+}
+
+impl fmt::Display for Replay {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		writeln!(f, "{}", Self::HEADER)?;
+		writeln!(f, "map {}", self.map)?;
+		writeln!(f, "seed {}", self.seed)?;
+		writeln!(f, "inputs")?;
+
+		let runs: Vec<String> = self.inputs
+			.chunk_by(|a, b| a == b)
+			.map(|run| format!("{}:{:02x}", run.len(), run[0].0))
+			.collect();
+		for line in runs.chunks(Self::RUNS_PER_LINE) {
+			writeln!(f, "{}", line.join(" "))?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::entity::Entities;
+	use crate::map::Map;
+	use crate::player::Player;
+	use crate::tiles::TileSet;
+
+	use std::rc::Rc;
+
+	fn scripted() -> Replay {
+		let mut replay = Replay::new("map1.txt", 42);
+		for tick in 0..600 {
+			let mut bits = 0;
+			if tick % 90 < 40 {
+				bits |= Input::RIGHT;
+			}
+			if tick % 120 == 60 {
+				bits |= Input::JUMP;
+			}
+			replay.record(Input(bits));
+		}
+		replay
+	}
+
+	// plays the inputs on the map and returns where the player ended up.
+	fn play(replay: &Replay) -> (f32, f32) {
+		let tile_set = Rc::new(TileSet::from_file(TileSet::PATH).unwrap());
+		let mut map = Map::from_file(&format!("maps/{}", replay.map), tile_set).unwrap();
+		let mut entities = Entities::spawn(&mut map);
+		let mut player = Player::new();
+		for tick in 0..replay.len() {
+			replay.input(tick).unwrap().apply(&mut player, &map);
+			player.update(&map);
+			entities.update(&map, &mut player);
+		}
+		(player.x, player.y)
+	}
+
+	#[test]
+	fn test_round_trip() {
+		let replay = scripted();
+		let text = replay.to_string();
+		assert!(text.starts_with("xrunner replay 1\nmap map1.txt\nseed 42\ninputs\n40:04 20:00 "));
+		assert_eq!(Replay::parse(&text), Ok(replay));
+	}
+
+	#[test]
+	fn test_playback_is_deterministic() {
+		let replay = Replay::parse(&scripted().to_string()).unwrap();
+		let end = play(&replay);
+		assert_eq!(end, play(&scripted()));
+		assert!(end.0 > Map::TILE_SIZE);
+	}
+
+	#[test]
+	fn test_script() {
+		let replay = Replay::from_script("map1.txt", "40*right 20*none 1*jump 29*none").unwrap();
+		assert_eq!(replay.len(), 90);
+		assert_eq!(replay.input(40), Some(Input(0)));
+		assert_eq!(replay.input(60), Some(Input(Input::JUMP)));
+		assert_eq!(Replay::from_script("map1.txt", "2*right+jump").unwrap().input(1), Some(Input(Input::RIGHT | Input::JUMP)));
+
+		assert_eq!(replay.script(), "40*right 20*none 1*jump 29*none");
+
+		assert!(Replay::from_script("map1.txt", "40right").is_err());
+		assert!(Replay::from_script("map1.txt", "40*sideways").is_err());
+	}
+
+	#[test]
+	fn test_malformed() {
+		assert!(Replay::parse("xrunner replay 2\n").is_err());
+		assert!(Replay::parse("xrunner replay 1\nmap map1.txt\nseed x\ninputs\n").is_err());
+		assert!(Replay::parse("xrunner replay 1\nmap map1.txt\nseed 1\ninputs\n10:zz\n").is_err());
+		assert!(Replay::parse("xrunner replay 1\nmap map1.txt\nseed 1\ninputs\n").unwrap().is_empty());
+	}
+}
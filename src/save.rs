@@ -0,0 +1,250 @@
+use crate::config::ConfigError;
+use crate::speedrun::{Run, Split};
+use crate::world::FIRST_MAP;
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+// Options picked by the player, kept in the save file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+	// 0.0 is silent, 1.0 full volume.
+	pub music_volume: f32,
+	pub effects_volume: f32,
+}
+
+impl Default for Settings {
+	fn default() -> Self {
+		Settings {
+			music_volume: 1.0,
+			effects_volume: 1.0,
+		}
+	}
+}
+
+impl Settings {
+
+	// the next louder volume in steps of a tenth, wrapping from full volume back to silent.
+	pub fn step_volume(volume: f32) -> f32 {
+		(((volume * 10.0).round() as i32 + 1) % 11) as f32 / 10.0
+	}
+}
+
+/*
+ * Progress through the maps and the settings, kept between runs in a TOML file. Every field has a
+ * default, so fields added later are simply filled in when an older save is loaded. Changes that
+ * can't be handled that way bump `VERSION` and convert older saves in `parse`. Saves from a newer
+ * version of the game are refused rather than overwritten with less.
+ * */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SaveGame {
+	pub version: u32,
+	// maps that can be picked in the level select, in the order they were unlocked.
+	pub unlocked: Vec<String>,
+	// maps left through a portal at least once.
+	pub completed: Vec<String>,
+	// most coins collected on a single pass through each map.
+	pub best_coins: BTreeMap<String, i32>,
+	pub settings: Settings,
+	// splits of the fastest finished run from each start map.
+	pub personal_bests: BTreeMap<String, Vec<Split>>,
+}
+
+impl Default for SaveGame {
+	fn default() -> Self {
+		SaveGame {
+			version: Self::VERSION,
+			unlocked: vec![FIRST_MAP.to_string()],
+			completed: Vec::new(),
+			best_coins: BTreeMap::new(),
+			settings: Settings::default(),
+			personal_bests: BTreeMap::new(),
+		}
+	}
+}
+
+impl SaveGame {
+
+	pub const PATH: &'static str = "saves/save.toml";
+
+	// version of the format written by this build.
+	pub const VERSION: u32 = 1;
+
+	pub fn load(path: &str) -> Result<Self, ConfigError> {
+		let content = std::fs::read_to_string(path).map_err(|err| ConfigError::MissingFile {
+			path: path.to_string(),
+			reason: err.to_string(),
+		})?;
+
+		Self::parse(&content).map_err(|reason| ConfigError::Invalid {
+			path: path.to_string(),
+			reason,
+		})
+	}
+
+	pub fn parse(content: &str) -> Result<Self, String> {
+		let table: toml::Table = toml::from_str(content).map_err(|err| err.to_string())?;
+		let version = match table.get("version").map(|version| version.as_integer()) {
+			Some(Some(version)) => version,
+			Some(None) => return Err(String::from("version is not a number")),
+			None => return Err(String::from("missing version")),
+		};
+		if version > Self::VERSION as i64 {
+			return Err(format!("saved by a newer version of the game (format {}, this one reads up to {})", version, Self::VERSION));
+		}
+		if version < 1 {
+			return Err(format!("unknown format {}", version));
+		}
+
+		let mut save: SaveGame = table.try_into().map_err(|err: toml::de::Error| err.to_string())?;
+		save.version = Self::VERSION;
+		Ok(save)
+	}
+
+	// the splits of the fastest run from `start`, empty if none finished yet.
+	pub fn personal_best(&self, start: &str) -> &[Split] {
+		self.personal_bests.get(start).map(Vec::as_slice).unwrap_or_default()
+	}
+
+	// keep the run if it is a new personal best, returns whether it was.
+	pub fn record_run(&mut self, run: &Run) -> bool {
+		let best = self.personal_bests.get(&run.start).map(Vec::as_slice);
+		if !run.beats(best) {
+			return false;
+		}
+		self.personal_bests.insert(run.start.clone(), run.splits().to_vec());
+		true
+	}
+
+	/*
+	 * Write the save to a temporary file next to `path` and move it over the old one, so a crash
+	 * while saving leaves either the old or the new save, never half of one.
+	 * */
+	pub fn save(&self, path: &str) -> std::io::Result<()> {
+		if let Some(dir) = Path::new(path).parent() {
+			std::fs::create_dir_all(dir)?;
+		}
+		let content = toml::to_string(self).map_err(std::io::Error::other)?;
+		let temporary = format!("{}.tmp", path);
+		std::fs::write(&temporary, content)?;
+		std::fs::rename(&temporary, path)
+	}
+
+	pub fn is_unlocked(&self, map: &str) -> bool {
+		self.unlocked.iter().any(|unlocked| unlocked == map)
+	}
+
+	pub fn is_completed(&self, map: &str) -> bool {
+		self.completed.iter().any(|completed| completed == map)
+	}
+
+	// coins over all maps counting the best pass through each, so replaying a map can't raise it forever.
+	pub fn total_coins(&self) -> i64 {
+		self.best_coins.values().map(|&coins| coins as i64).sum()
+	}
+
+	// the map unlocked last, where playing on continues.
+	pub fn last_unlocked(&self) -> &str {
+		self.unlocked.last().map(String::as_str).unwrap_or(FIRST_MAP)
+	}
+
+	// `map` was left with `coins` collected on it, unlocking the map `next` it leads to if any.
+	pub fn complete_level(&mut self, map: &str, coins: i32, next: Option<&str>) {
+		if !self.is_completed(map) {
+			self.completed.push(map.to_string());
+		}
+		let best = self.best_coins.entry(map.to_string()).or_insert(0);
+		*best = (*best).max(coins);
+
+		if let Some(next) = next {
+			if !self.is_unlocked(next) {
+				self.unlocked.push(next.to_string());
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_progress_round_trip() {
+		let mut save = SaveGame::default();
+		save.complete_level("map1.txt", 12, Some("map2.txt"));
+		save.complete_level("map1.txt", 7, Some("map2.txt"));
+		save.complete_level("map2.txt", 3, None);
+		save.settings.music_volume = 0.5;
+
+		let mut run = Run::new("map1.txt");
+		run.tick();
+		run.split("map1.txt");
+		run.tick();
+		run.finish("map2.txt");
+		assert!(save.record_run(&run));
+		assert!(!save.record_run(&run));
+		assert_eq!(save.personal_best("map1.txt"), run.splits());
+		assert!(save.personal_best("map2.txt").is_empty());
+
+		assert_eq!(save.unlocked, vec!["map1.txt", "map2.txt"]);
+		assert_eq!(save.last_unlocked(), "map2.txt");
+		assert!(save.is_completed("map2.txt"));
+		assert_eq!(save.best_coins["map1.txt"], 12);
+		assert_eq!(save.total_coins(), 15);
+
+		let text = toml::to_string(&save).unwrap();
+		assert_eq!(SaveGame::parse(&text), Ok(save));
+	}
+
+	#[test]
+	fn test_replaying_adds_only_improvements() {
+		let mut save = SaveGame::default();
+		save.complete_level("map1.txt", 5, Some("map2.txt"));
+		save.complete_level("map1.txt", 5, Some("map2.txt"));
+		save.complete_level("map1.txt", 2, Some("map2.txt"));
+		assert_eq!(save.total_coins(), 5);
+		save.complete_level("map1.txt", 9, Some("map2.txt"));
+		assert_eq!(save.total_coins(), 9);
+		assert_eq!(save.best_coins["map1.txt"], 9);
+	}
+
+	#[test]
+	fn test_step_volume() {
+		assert_eq!(Settings::step_volume(0.0), 0.1);
+		assert_eq!(Settings::step_volume(0.5), 0.6);
+		assert_eq!(Settings::step_volume(0.9), 1.0);
+		assert_eq!(Settings::step_volume(1.0), 0.0);
+	}
+
+	#[test]
+	fn test_versions() {
+		// fields missing from an older save get their defaults.
+		let save = SaveGame::parse("version = 1\nunlocked = [\"map1.txt\", \"map3.txt\"]\n").unwrap();
+		assert_eq!(save.last_unlocked(), "map3.txt");
+		assert_eq!(save.settings, Settings::default());
+
+		assert!(SaveGame::parse("version = 2\n").is_err());
+		assert!(SaveGame::parse("unlocked = []\n").is_err());
+		assert!(SaveGame::parse("version = 1\nbest_coins = \"many\"\n").is_err());
+	}
+
+	#[test]
+	fn test_save_replaces_file() {
+		let dir = std::env::temp_dir().join(format!("xrunner-save-{}", std::process::id()));
+		let path = dir.join("save.toml");
+		let path = path.to_str().unwrap();
+
+		let mut save = SaveGame::default();
+		save.save(path).unwrap();
+		save.complete_level("map1.txt", 5, Some("map2.txt"));
+		save.save(path).unwrap();
+
+		assert_eq!(SaveGame::load(path), Ok(save));
+		assert!(!Path::new(&format!("{}.tmp", path)).exists());
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+}
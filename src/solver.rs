@@ -0,0 +1,350 @@
+use crate::entity::{Entities, EntityId};
+use crate::input::Input;
+use crate::map::{Aabb, Map};
+use crate::player::Player;
+use crate::tiles::Touch;
+
+use std::collections::{HashSet, VecDeque};
+
+// Something on the map the search tries to touch: a coin, gravity pickup or portal.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Target {
+	pub id: EntityId,
+	pub tile: char,
+	pub touch: Touch,
+	pub tile_x: usize,
+	pub tile_y: usize,
+	// the shortest input found that touches it, None if it wasn't reached.
+	pub path: Option<Vec<Input>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Reachability {
+	pub targets: Vec<Target>,
+	// number of distinct states explored.
+	pub states: usize,
+	// false if the search stopped at its limit, unreached targets might still be reachable.
+	pub complete: bool,
+}
+
+// A touchable entity during the search.
+struct Touchable {
+	bounds: Aabb,
+	touch: Touch,
+	// bit in `Node::used` or `Node::unarmed`, gravity pickups and portals only.
+	bit: usize,
+	// index in `Reachability::targets`, None for hazards.
+	target: Option<usize>,
+}
+
+// A bit for every gravity pickup and portal, as many as the map has.
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct Flags(Vec<u64>);
+
+impl Flags {
+
+	fn new(count: usize) -> Self {
+		Flags(vec![0; count.div_ceil(64)])
+	}
+
+	fn get(&self, bit: usize) -> bool {
+		self.0[bit / 64] & (1 << (bit % 64)) != 0
+	}
+
+	fn set(&mut self, bit: usize, value: bool) {
+		if value {
+			self.0[bit / 64] |= 1 << (bit % 64);
+		} else {
+			self.0[bit / 64] &= !(1 << (bit % 64));
+		}
+	}
+}
+
+#[derive(Clone)]
+struct Node {
+	player: Player,
+	// gravity pickups used up.
+	used: Flags,
+	// portals the player stood on at the start and hasn't left yet.
+	unarmed: Flags,
+	// the node this one was reached from and the input held on the way.
+	parent: usize,
+	input: Input,
+}
+
+// Identifies a state exactly, nearby states can't be merged as being a fraction of a pixel
+// higher can be the difference between standing and not being able to jump.
+type Key = (u32, u32, u32, u32, bool, Flags, Flags);
+
+impl Node {
+
+	fn key(&self) -> Key {
+		let player = &self.player;
+		(
+			player.x.to_bits(),
+			player.y.to_bits(),
+			player.vx.to_bits(),
+			player.vy.to_bits(),
+			player.has_gravity(),
+			self.used.clone(),
+			self.unarmed.clone(),
+		)
+	}
+}
+
+// the inputs tried every tick.
+const ACTIONS: [u8; 8] = [
+	0,
+	Input::LEFT,
+	Input::RIGHT,
+	Input::JUMP,
+	Input::JUMP | Input::LEFT,
+	Input::JUMP | Input::RIGHT,
+	Input::UP,
+	Input::DOWN,
+];
+
+/*
+ * Breadth first search over everything the player can do on `map` starting as `start`, trying each
+ * of `ACTIONS` every tick with the real player physics, so paths are the fewest ticks. Touching a
+ * portal ends a path, touching a hazard kills it. Enemies are ignored as where they are depends on
+ * time.
+ * */
+pub fn search(map: &Map, entities: &Entities, start: Player, max_states: usize) -> Reachability {
+	let mut targets: Vec<Target> = Vec::new();
+	let mut touchables: Vec<Touchable> = Vec::new();
+	let mut tracked = 0;
+	for (id, entity) in entities.iter() {
+		let Some(touch) = map.tile_set().touch(entity.tile()) else {
+			continue;
+		};
+		let bounds = entity.bounds();
+		let mut touchable = Touchable { bounds, touch, bit: 0, target: None };
+		if matches!(touch, Touch::Gravity | Touch::Portal) {
+			touchable.bit = tracked;
+			tracked += 1;
+		}
+		if touch != Touch::Kill {
+			touchable.target = Some(targets.len());
+			targets.push(Target {
+				id,
+				tile: entity.tile(),
+				touch,
+				tile_x: (bounds.x / Map::TILE_SIZE) as usize,
+				tile_y: (bounds.y / Map::TILE_SIZE) as usize,
+				path: None,
+			});
+		}
+		touchables.push(touchable);
+	}
+
+	let start_touch = start.touch_bounds();
+	let mut unarmed = Flags::new(tracked);
+	for touchable in touchables.iter().filter(|touchable| touchable.touch == Touch::Portal && touchable.bounds.overlaps(&start_touch)) {
+		unarmed.set(touchable.bit, true);
+	}
+	let root = Node { player: start, used: Flags::new(tracked), unarmed, parent: 0, input: Input::default() };
+
+	let mut seen: HashSet<Key> = HashSet::from([root.key()]);
+	let mut nodes = vec![root];
+	let mut queue = VecDeque::from([0]);
+	let mut complete = true;
+
+	while let Some(index) = queue.pop_front() {
+		if nodes.len() >= max_states {
+			complete = false;
+			break;
+		}
+
+		for bits in ACTIONS {
+			let input = Input(bits);
+			let mut node = nodes[index].clone();
+			node.parent = index;
+			node.input = input;
+
+			input.apply(&mut node.player, map);
+			node.player.update(map);
+
+			let touch = node.player.touch_bounds();
+			let mut alive = true;
+			let mut left_map = false;
+			let mut reached: Vec<usize> = Vec::new();
+			// gravity pickups touched together toggle once, like `Entities::update`.
+			let mut toggled = false;
+			for touchable in &touchables {
+				let overlapping = touchable.bounds.overlaps(&touch);
+				match touchable.touch {
+					Touch::Portal if !overlapping => node.unarmed.set(touchable.bit, false),
+					_ if !overlapping => {}
+					Touch::Kill => alive = false,
+					Touch::Collect | Touch::Checkpoint => reached.extend(touchable.target),
+					Touch::Gravity if !node.used.get(touchable.bit) => {
+						if !toggled {
+							node.player.gravity_toggle();
+							toggled = true;
+						}
+						node.used.set(touchable.bit, true);
+						reached.extend(touchable.target);
+					}
+					Touch::Portal if !node.unarmed.get(touchable.bit) => {
+						left_map = true;
+						reached.extend(touchable.target);
+					}
+					Touch::Gravity | Touch::Portal => {}
+				}
+			}
+
+			for target in reached {
+				if targets[target].path.is_none() {
+					let mut path = path_to(&nodes, index);
+					path.push(input);
+					targets[target].path = Some(path);
+				}
+			}
+
+			if alive && !left_map && seen.insert(node.key()) {
+				nodes.push(node);
+				queue.push_back(nodes.len() - 1);
+			}
+		}
+	}
+
+	Reachability { targets, states: nodes.len(), complete }
+}
+
+// the inputs of every tick from the start to a node.
+fn path_to(nodes: &[Node], mut index: usize) -> Vec<Input> {
+	let mut path = Vec::new();
+	while index != 0 {
+		path.push(nodes[index].input);
+		index = nodes[index].parent;
+	}
+	path.reverse();
+	path
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::command::Command;
+	use crate::tiles::TileSet;
+	use crate::world::{map_file, map_names, FIRST_MAP};
+
+	use std::rc::Rc;
+
+	fn spawn(content: &str) -> (Map, Entities) {
+		let tile_set = TileSet::from_file(TileSet::PATH).unwrap();
+		let mut map = Map::parse_map(content, Rc::new(tile_set)).unwrap();
+		let entities = Entities::spawn(&mut map);
+		(map, entities)
+	}
+
+	fn player_at(tile_x: usize, tile_y: usize) -> Player {
+		let mut player = Player::new();
+		player.x = tile_x as f32 * Map::TILE_SIZE;
+		player.y = tile_y as f32 * Map::TILE_SIZE;
+		player
+	}
+
+	fn reached(reachability: &Reachability, tile: char) -> Vec<bool> {
+		reachability.targets.iter()
+			.filter(|target| target.tile == tile)
+			.map(|target| target.path.is_some())
+			.collect()
+	}
+
+	#[test]
+	fn test_walled_in_coin() {
+		let (map, entities) = spawn("xxxxxxxxxx\nx      xcx\nx c   pxxx\nxxxxxxxxxx\n\n[p] map2.txt\n");
+		let reachability = search(&map, &entities, player_at(1, 1), 100_000);
+		assert!(reachability.complete);
+		assert_eq!(reached(&reachability, 'c'), vec![false, true]);
+		assert_eq!(reached(&reachability, 'p'), vec![true]);
+	}
+
+	#[test]
+	fn test_path_reaches_portal() {
+		let (map, mut entities) = spawn("xxxxxxxxxx\nx        x\nx   x   px\nxxxxxxxxxx\n\n[p] map2.txt\n");
+		let reachability = search(&map, &entities, player_at(1, 1), 100_000);
+		let path = reachability.targets[0].path.clone().unwrap();
+
+		// playing the path for real ends on the portal.
+		let mut player = player_at(1, 1);
+		let mut commands = Vec::new();
+		for input in path {
+			input.apply(&mut player, &map);
+			player.update(&map);
+			commands = entities.update(&map, &mut player);
+		}
+		assert_eq!(commands, vec![Command::LoadMap(String::from("map2.txt"), 'p')]);
+	}
+
+	#[test]
+	fn test_ceiling_needs_gravity_pickup() {
+		let room = "xxxxxxx\nx    cx\nx     x\nx     x\nx     x\nx     x\nx     x\nx  g  x\nxxxxxxx\n";
+		let (map, entities) = spawn(room);
+		let reachability = search(&map, &entities, player_at(1, 7), 100_000);
+		assert_eq!(reached(&reachability, 'g'), vec![true]);
+		assert_eq!(reached(&reachability, 'c'), vec![true]);
+
+		// too high to jump to without it.
+		let (map, entities) = spawn(&room.replace('g', " "));
+		let reachability = search(&map, &entities, player_at(1, 7), 100_000);
+		assert_eq!(reached(&reachability, 'c'), vec![false]);
+	}
+
+	#[test]
+	fn test_more_than_64_pickups_and_portals() {
+		// 64 gravity pickups walled off come first, the portal after them is still told apart:
+		// starting on it boxed in, it can't be left and entered again.
+		let rows = ["x".repeat(66), format!("x{}x", "g".repeat(64)), "x".repeat(66), format!("xpx{}", "x".repeat(63)), "x".repeat(66)];
+		let (map, entities) = spawn(&(rows.join("\n") + "\n\n[p] map2.txt\n"));
+		let reachability = search(&map, &entities, player_at(1, 3), 100_000);
+		assert!(reachability.complete);
+		assert_eq!(reached(&reachability, 'p'), vec![false]);
+	}
+
+	#[test]
+	fn test_every_portal_reachable() {
+		let tile_set = Rc::new(TileSet::from_file(TileSet::PATH).unwrap());
+		let maps: Vec<(String, Map, Entities)> = map_names().into_iter()
+			.map(|name| {
+				let mut map = Map::from_file(&map_file(&name), tile_set.clone()).unwrap();
+				let entities = Entities::spawn(&mut map);
+				(name, map, entities)
+			})
+			.collect();
+
+		for (name, map, entities) in &maps {
+			// arriving through a portal starts on the one with the same char, or where a new game does.
+			let mut entries: Vec<Option<char>> = maps.iter()
+				.flat_map(|(_, other, _)| other.mappings())
+				.filter(|(_, target)| target == name)
+				.map(|(portal, _)| entities.find(*portal).map(|_| *portal))
+				.collect();
+			if name == FIRST_MAP {
+				entries.push(None);
+			}
+			entries.dedup();
+
+			let mut reached: Vec<(char, Option<char>)> = Vec::new();
+			for &entry in &entries {
+				let mut player = Player::new();
+				if let Some((x, y)) = entry.and_then(|portal| entities.find(portal)) {
+					player.x = x;
+					player.y = y;
+				}
+				let reachability = search(map, entities, player, 2_000_000);
+				reached.extend(reachability.targets.iter()
+					.filter(|target| target.touch == Touch::Portal && target.path.is_some())
+					.map(|target| (target.tile, entry)));
+			}
+
+			// stepping back onto the portal just arrived on only counts when it's the only way in.
+			for (portal, _) in map.mappings() {
+				let found = reached.iter().any(|&(tile, entry)| tile == *portal && (entry != Some(tile) || entries.len() == 1));
+				assert!(found, "portal '{}' of {} can't be reached", portal, name);
+			}
+		}
+	}
+}
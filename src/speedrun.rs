@@ -0,0 +1,214 @@
+use crate::player::Player;
+
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use std::path::Path;
+
+// The time a map was left at, counted from the start of the run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Split {
+	pub map: String,
+	pub ticks: u64,
+}
+
+/*
+ * Times a run through the maps in simulation ticks, so pauses, slow frames and replays don't
+ * change it. A split is taken every time a map is left through a portal, the run finishes when a
+ * portal leads out of the last map.
+ * */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Run {
+	// the map the run started on, personal bests are kept per start.
+	pub start: String,
+	ticks: u64,
+	splits: Vec<Split>,
+	finished: bool,
+}
+
+impl Run {
+
+	// splits shown in the HUD.
+	const SHOWN_SPLITS: usize = 5;
+
+	pub fn new(start: &str) -> Self {
+		Run {
+			start: start.to_string(),
+			ticks: 0,
+			splits: Vec::new(),
+			finished: false,
+		}
+	}
+
+	// count one tick, the clock stops once the run is finished.
+	pub fn tick(&mut self) {
+		if !self.finished {
+			self.ticks += 1;
+		}
+	}
+
+	pub fn ticks(&self) -> u64 {
+		self.ticks
+	}
+
+	pub fn splits(&self) -> &[Split] {
+		&self.splits
+	}
+
+	pub fn is_finished(&self) -> bool {
+		self.finished
+	}
+
+	// `map` was left through a portal.
+	pub fn split(&mut self, map: &str) {
+		if !self.finished {
+			self.splits.push(Split { map: map.to_string(), ticks: self.ticks });
+		}
+	}
+
+	// `map` was left through a portal to a map that doesn't exist, the run is over.
+	pub fn finish(&mut self, map: &str) {
+		self.split(map);
+		self.finished = true;
+	}
+
+	// ticks ahead (negative) or behind (positive) the personal best at split `index`, None if
+	// the best run went a different way.
+	pub fn delta(&self, index: usize, best: &[Split]) -> Option<i64> {
+		let split = self.splits.get(index)?;
+		let best = best.get(index).filter(|best| best.map == split.map)?;
+		Some(split.ticks as i64 - best.ticks as i64)
+	}
+
+	// true if the run is finished and faster than `best`, or there is no best yet.
+	pub fn beats(&self, best: Option<&[Split]>) -> bool {
+		match best.and_then(|best| best.last()) {
+			Some(best) => self.finished && self.ticks < best.ticks,
+			None => self.finished,
+		}
+	}
+
+	// the splits as CSV, with the time of every map on its own and the difference to `best`.
+	pub fn to_csv(&self, best: &[Split]) -> String {
+		let mut csv = String::from("split,map,time,segment,personal best,delta\n");
+		let mut previous = 0;
+		for (index, split) in self.splits.iter().enumerate() {
+			let best_time = best.get(index).filter(|best| best.map == split.map).map(|best| format_ticks(best.ticks));
+			let delta = self.delta(index, best).map(format_delta);
+			csv.push_str(&format!(
+				"{},{},{},{},{},{}\n",
+				index + 1,
+				split.map,
+				format_ticks(split.ticks),
+				format_ticks(split.ticks - previous),
+				best_time.unwrap_or_default(),
+				delta.unwrap_or_default(),
+			));
+			previous = split.ticks;
+		}
+		csv
+	}
+
+	pub fn export(&self, path: &str, best: &[Split]) -> std::io::Result<()> {
+		if let Some(dir) = Path::new(path).parent() {
+			std::fs::create_dir_all(dir)?;
+		}
+		std::fs::write(path, self.to_csv(best))
+	}
+
+	// the clock and the last splits in the top right corner, in screen coordinates.
+	pub fn draw(&self, best: &[Split]) {
+		let right = screen_width() - 20.0;
+		let clock = format_ticks(self.ticks);
+		let color = if self.finished { GOLD } else { WHITE };
+		draw_text(clock.as_str(), right - measure_text(&clock, None, 40, 1.0).width, 90.0, 40.0, color);
+
+		let first = self.splits.len().saturating_sub(Self::SHOWN_SPLITS);
+		let mut y = 118.0;
+		for (index, split) in self.splits.iter().enumerate().skip(first) {
+			let (delta, color) = match self.delta(index, best) {
+				Some(delta) if delta <= 0 => (format_delta(delta), GREEN),
+				Some(delta) => (format_delta(delta), RED),
+				None => (String::new(), WHITE),
+			};
+			let line = format!("{}  {}", split.map, format_ticks(split.ticks));
+			draw_text(line.as_str(), right - 120.0 - measure_text(&line, None, 22, 1.0).width, y, 22.0, WHITE);
+			draw_text(delta.as_str(), right - measure_text(&delta, None, 22, 1.0).width, y, 22.0, color);
+			y += 24.0;
+		}
+	}
+}
+
+// ticks as minutes, seconds and milliseconds, e.g. "1:05.250".
+pub fn format_ticks(ticks: u64) -> String {
+	let millis = ticks * 1000 / Player::TICK_RATE as u64;
+	format!("{}:{:02}.{:03}", millis / 60_000, millis / 1000 % 60, millis % 1000)
+}
+
+// a difference in ticks with its sign, e.g. "-0:01.500".
+pub fn format_delta(delta: i64) -> String {
+	let sign = if delta < 0 { '-' } else { '+' };
+	format!("{}{}", sign, format_ticks(delta.unsigned_abs()))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn run(splits: &[(&str, u64)], finished: bool) -> Run {
+		let mut run = Run::new("map1.txt");
+		for (map, ticks) in splits {
+			while run.ticks() < *ticks {
+				run.tick();
+			}
+			run.split(map);
+		}
+		run.finished = finished;
+		run
+	}
+
+	#[test]
+	fn test_format() {
+		assert_eq!(format_ticks(0), "0:00.000");
+		assert_eq!(format_ticks(90), "0:01.500");
+		assert_eq!(format_ticks(60 * 75 + 15), "1:15.250");
+		assert_eq!(format_delta(-30), "-0:00.500");
+		assert_eq!(format_delta(0), "+0:00.000");
+	}
+
+	#[test]
+	fn test_splits_against_best() {
+		let best = run(&[("map1.txt", 600), ("map2.txt", 1200), ("map3.txt", 2000)], true);
+		let mut current = run(&[("map1.txt", 570), ("map2.txt", 1260)], false);
+
+		assert_eq!(current.delta(0, best.splits()), Some(-30));
+		assert_eq!(current.delta(1, best.splits()), Some(60));
+		assert!(!current.beats(Some(best.splits())));
+
+		// the clock stops at the finish.
+		while current.ticks() < 1990 {
+			current.tick();
+		}
+		current.finish("map3.txt");
+		current.tick();
+		assert_eq!(current.ticks(), 1990);
+		assert!(current.beats(Some(best.splits())));
+		assert!(current.beats(None));
+
+		// a run that went another way has nothing to compare with.
+		let other = run(&[("map1.txt", 500), ("map4.txt", 900)], false);
+		assert_eq!(other.delta(1, best.splits()), None);
+	}
+
+	#[test]
+	fn test_csv() {
+		let best = run(&[("map1.txt", 600), ("map2.txt", 1200)], true);
+		let current = run(&[("map1.txt", 570), ("map2.txt", 1260)], true);
+		assert_eq!(
+			current.to_csv(best.splits()),
+			"split,map,time,segment,personal best,delta\n\
+			1,map1.txt,0:09.500,0:09.500,0:10.000,-0:00.500\n\
+			2,map2.txt,0:21.000,0:11.500,0:20.000,+0:01.000\n",
+		);
+	}
+}
@@ -0,0 +1,77 @@
+use crate::player::Player;
+
+// Turns the time frames took into simulation ticks of `Player::TICK_TIME`, so the game plays the
+// same at any frame rate. Time short of a tick is carried over to the next frame.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Ticker {
+	accumulator: f32,
+}
+
+impl Ticker {
+
+	// add the time the last frame took.
+	pub fn add(&mut self, frame_time: f32) {
+		self.accumulator += frame_time;
+	}
+
+	// take one tick from the time added, false once less than a tick is left.
+	pub fn next_tick(&mut self) -> bool {
+		if self.accumulator < Player::TICK_TIME {
+			return false;
+		}
+		self.accumulator -= Player::TICK_TIME;
+		true
+	}
+
+	// how far we are between the last tick and the next, from 0 to 1.
+	pub fn alpha(&self) -> f32 {
+		self.accumulator / Player::TICK_TIME
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::map::Map;
+	use crate::tiles::TileSet;
+	use crate::world::map_file;
+
+	use std::rc::Rc;
+
+	// play map1 for `seconds` in frames of `frame_time`, the player's position after every tick.
+	fn run(frame_time: f32, seconds: f32) -> Vec<(f32, f32)> {
+		let tile_set = Rc::new(TileSet::from_file(TileSet::PATH).unwrap());
+		let map = Map::from_file(&map_file("map1.txt"), tile_set).unwrap();
+		let mut ticker = Ticker::default();
+		let mut player = Player::new();
+		let mut positions = Vec::new();
+		for _ in 0..(seconds / frame_time).round() as usize {
+			ticker.add(frame_time);
+			while ticker.next_tick() {
+				let tick = positions.len();
+				if tick % 90 < 40 {
+					player.move_right();
+				}
+				if tick % 120 == 60 {
+					player.jump(&map);
+				}
+				player.update(&map);
+				positions.push((player.x, player.y));
+			}
+		}
+		positions
+	}
+
+	#[test]
+	fn test_same_ticks_at_any_frame_rate() {
+		let slow = run(1.0 / 30.0, 10.0);
+		let fast = run(1.0 / 144.0, 10.0);
+
+		// rounding may leave the last tick for a frame that didn't come.
+		assert!(slow.len().abs_diff(fast.len()) <= 1);
+		let ticks = slow.len().min(fast.len());
+		assert!(ticks >= 599);
+		assert_eq!(slow[..ticks], fast[..ticks]);
+		assert!(slow[ticks - 1].0 > Map::TILE_SIZE);
+	}
+}
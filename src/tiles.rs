@@ -0,0 +1,225 @@
+use crate::animation::Animation;
+use crate::autotile::Autotile;
+use crate::config::ConfigError;
+
+use serde::Deserialize;
+use std::collections::HashMap;
+
+// What happens when the player touches a tile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Touch {
+	Collect,
+	Kill,
+	Gravity,
+	Portal,
+	Checkpoint,
+}
+
+// How an enemy moves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EnemyKind {
+	Walker,
+	Flyer,
+	Chaser,
+}
+
+// The meaning of one map character, see tiles.toml.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TileDef {
+	pub char: char,
+	#[serde(default)]
+	pub sprite: Option<(i32, i32)>,
+	#[serde(default)]
+	pub rotation: f32,
+	#[serde(default)]
+	pub color: Option<(u8, u8, u8)>,
+	// name of the autotile rules picking the sprite from the neighbours.
+	#[serde(default)]
+	pub autotile: Option<String>,
+	// name of the animation cycled through instead of showing the sprite.
+	#[serde(default)]
+	pub animation: Option<String>,
+	// the animation looked up by `TileSet::parse`.
+	#[serde(skip)]
+	pub animated: Option<Animation>,
+	#[serde(default)]
+	pub solid: bool,
+	#[serde(default)]
+	pub empty: bool,
+	#[serde(default)]
+	pub touch: Option<Touch>,
+	#[serde(default)]
+	pub enemy: Option<EnemyKind>,
+	#[serde(default)]
+	pub stompable: bool,
+}
+
+impl TileDef {
+
+	// the cell of the tile sheet to draw `time` seconds into the animation, or the sprite.
+	pub fn cell(&self, time: f32) -> Option<(i32, i32)> {
+		match &self.animated {
+			Some(animation) => Some(animation.frame(time)),
+			None => self.sprite,
+		}
+	}
+}
+
+#[derive(Deserialize)]
+struct TileFile {
+	tile: Vec<TileDef>,
+	#[serde(default)]
+	autotile: Vec<Autotile>,
+	#[serde(default)]
+	animation: Vec<Animation>,
+}
+
+// All tile definitions, loaded from tiles.toml.
+#[derive(Debug, Clone, Default)]
+pub struct TileSet {
+	defs: HashMap<char, TileDef>,
+	// definition order.
+	order: Vec<char>,
+	solids: Vec<char>,
+	autotiles: HashMap<String, Autotile>,
+	animations: HashMap<String, Animation>,
+}
+
+impl TileSet {
+
+	pub const PATH: &'static str = "tiles.toml";
+
+	pub fn from_file(path: &str) -> Result<Self, ConfigError> {
+		let content = std::fs::read_to_string(path).map_err(|err| ConfigError::MissingFile {
+			path: path.to_string(),
+			reason: err.to_string(),
+		})?;
+
+		Self::parse(&content).map_err(|reason| ConfigError::Invalid {
+			path: path.to_string(),
+			reason,
+		})
+	}
+
+	pub fn parse(content: &str) -> Result<Self, String> {
+		let file: TileFile = toml::from_str(content).map_err(|err| err.to_string())?;
+
+		let mut tile_set = TileSet::default();
+		for mut autotile in file.autotile {
+			autotile.compile()?;
+			tile_set.autotiles.insert(autotile.name.clone(), autotile);
+		}
+
+		for animation in file.animation {
+			animation.check()?;
+			tile_set.animations.insert(animation.name.clone(), animation);
+		}
+
+		for mut def in file.tile {
+			if let Some(name) = &def.autotile {
+				if !tile_set.autotiles.contains_key(name) {
+					return Err(format!("tile '{}' uses unknown autotile '{}'", def.char, name));
+				}
+			}
+			if let Some(name) = &def.animation {
+				match tile_set.animations.get(name) {
+					Some(animation) => def.animated = Some(animation.clone()),
+					None => return Err(format!("tile '{}' uses unknown animation '{}'", def.char, name)),
+				}
+			}
+			if def.enemy.is_some() && (def.solid || def.touch.is_some()) {
+				return Err(format!("enemy tile '{}' can't be solid or have a touch behaviour", def.char));
+			}
+			if tile_set.defs.contains_key(&def.char) {
+				return Err(format!("tile '{}' is defined twice", def.char));
+			}
+			if def.solid {
+				tile_set.solids.push(def.char);
+			}
+			tile_set.order.push(def.char);
+			tile_set.defs.insert(def.char, def);
+		}
+
+		Ok(tile_set)
+	}
+
+	// characters of all tiles in definition order.
+	pub fn chars(&self) -> &[char] {
+		&self.order
+	}
+
+	pub fn get(&self, tile: char) -> Option<&TileDef> {
+		self.defs.get(&tile)
+	}
+
+	pub fn contains(&self, tile: char) -> bool {
+		self.defs.contains_key(&tile)
+	}
+
+	// characters of all tiles that block movement.
+	pub fn solids(&self) -> &[char] {
+		&self.solids
+	}
+
+	pub fn touch(&self, tile: char) -> Option<Touch> {
+		self.get(tile).and_then(|def| def.touch)
+	}
+
+	// the autotile rules of a tile, if it has any.
+	pub fn autotile(&self, tile: char) -> Option<&Autotile> {
+		self.get(tile)
+			.and_then(|def| def.autotile.as_ref())
+			.and_then(|name| self.autotiles.get(name))
+	}
+
+	// an animation by name, e.g. one of the player's.
+	pub fn animation(&self, name: &str) -> Option<&Animation> {
+		self.animations.get(name)
+	}
+
+	pub fn is_empty_space(&self, tile: char) -> bool {
+		self.get(tile).is_some_and(|def| def.empty)
+	}
+
+	// the empty tile left behind when something is removed from the map.
+	pub fn blank(&self) -> char {
+		self.order.iter().copied().find(|&tile| self.is_empty_space(tile)).unwrap_or(' ')
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_load_tiles_toml() {
+		let tile_set = TileSet::from_file(TileSet::PATH).unwrap();
+		assert_eq!(tile_set.solids(), &['x']);
+		assert_eq!(tile_set.touch('c'), Some(Touch::Collect));
+		assert_eq!(tile_set.touch('p'), Some(Touch::Portal));
+		assert_eq!(tile_set.get('f').unwrap().sprite, Some((1, 7)));
+		assert!(tile_set.is_empty_space(' '));
+		assert!(!tile_set.contains('z'));
+		assert_eq!(tile_set.autotile('x').unwrap().name, "wall");
+		assert!(tile_set.autotile('c').is_none());
+		assert_eq!(tile_set.get('w').unwrap().enemy, Some(EnemyKind::Walker));
+		assert!(!tile_set.get('h').unwrap().stompable);
+		assert_eq!(tile_set.get('c').unwrap().cell(0.0), Some((0, 6)));
+		assert_eq!(tile_set.get('x').unwrap().cell(0.0), None);
+		assert!(tile_set.animation("player_run").is_some());
+	}
+
+	#[test]
+	fn test_invalid_definitions() {
+		assert!(TileSet::parse("[[tile]]\nchar = \"x\"\n[[tile]]\nchar = \"x\"\n").is_err());
+		assert!(TileSet::parse("[[tile]]\nchar = \"x\"\ntouch = \"explode\"\n").is_err());
+		assert!(TileSet::parse("[[tile]]\nchar = \"xy\"\n").is_err());
+		assert!(TileSet::parse("[[tile]]\nchar = \"x\"\nautotile = \"rock\"\n").is_err());
+		assert!(TileSet::parse("[[tile]]\nchar = \"x\"\nanimation = \"spin\"\n").is_err());
+		assert!(TileSet::parse("[[animation]]\nname = \"spin\"\nframes = []\nduration = 0.1\n").is_err());
+		assert!(TileSet::parse("[[tile]]\nchar = \"w\"\nenemy = \"walker\"\nsolid = true\n").is_err());
+	}
+}
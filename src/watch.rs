@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+// Notices files added to or changed in some directories by comparing modification times every
+// time it is polled, so it needs no platform specific file system events. Subdirectories aren't
+// watched.
+pub struct Watcher {
+	dirs: Vec<PathBuf>,
+	stamps: HashMap<PathBuf, SystemTime>,
+}
+
+impl Watcher {
+
+	// seconds between polls in the game, reading the directories every frame is wasteful.
+	pub const POLL_INTERVAL: f64 = 0.5;
+
+	pub fn new(dirs: &[&str]) -> Self {
+		let mut watcher = Watcher {
+			dirs: dirs.iter().map(PathBuf::from).collect(),
+			stamps: HashMap::new(),
+		};
+		watcher.stamps = watcher.scan().into_iter().collect();
+		watcher
+	}
+
+	// files added or modified since the last poll, sorted by path.
+	pub fn poll(&mut self) -> Vec<PathBuf> {
+		let mut changed = Vec::new();
+		for (path, stamp) in self.scan() {
+			if self.stamps.insert(path.clone(), stamp) != Some(stamp) {
+				changed.push(path);
+			}
+		}
+		changed.sort();
+		changed
+	}
+
+	// the files in the directories and when they were last modified, unreadable ones are skipped.
+	fn scan(&self) -> Vec<(PathBuf, SystemTime)> {
+		let mut files = Vec::new();
+		for dir in &self.dirs {
+			let Ok(entries) = std::fs::read_dir(dir) else {
+				continue;
+			};
+			for entry in entries.flatten() {
+				let Ok(metadata) = entry.metadata() else {
+					continue;
+				};
+				if let (true, Ok(modified)) = (metadata.is_file(), metadata.modified()) {
+					files.push((entry.path(), modified));
+				}
+			}
+		}
+		files
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use std::fs::File;
+	use std::time::Duration;
+
+	#[test]
+	fn test_poll_reports_changes() {
+		let dir = std::env::temp_dir().join(format!("xrunner-watch-{}", std::process::id()));
+		std::fs::create_dir_all(&dir).unwrap();
+		let map = dir.join("map1.txt");
+		std::fs::write(&map, "x\n").unwrap();
+
+		let mut watcher = Watcher::new(&[dir.to_str().unwrap()]);
+		assert!(watcher.poll().is_empty());
+
+		// file systems can store times coarsely, so date the change a second ahead.
+		std::fs::write(&map, "xx\n").unwrap();
+		File::options().write(true).open(&map).unwrap().set_modified(SystemTime::now() + Duration::from_secs(1)).unwrap();
+		let added = dir.join("map2.txt");
+		std::fs::write(&added, "x\n").unwrap();
+		assert_eq!(watcher.poll(), vec![map, added]);
+		assert!(watcher.poll().is_empty());
+
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+}
@@ -0,0 +1,537 @@
+use crate::command::Command;
+use crate::entity::{Entities, Origin};
+use crate::input::Input;
+use crate::map::{Map, MapError};
+use crate::player::Player;
+use crate::tiles::{TileSet, Touch};
+
+use std::path::Path;
+use std::rc::Rc;
+
+// What executing a command changed, for the game or the headless runner to react to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+	// went through `portal` to `map`, the player stands on its exit portal.
+	EnteredPortal { portal: char, map: String },
+	// `portal` leads to a map that doesn't exist, the game is won.
+	Won { portal: char, map: String },
+	// killed by the entity spawned from `tile` at (tile_x, tile_y), the player respawns
+	// `World::DEATH_TICKS` later.
+	Died { tile: char, tile_x: usize, tile_y: usize },
+	// back at the spawn after dying, the map is as it was at the last checkpoint or when it was entered.
+	Respawned,
+	// the player respawns at (x, y) from now on.
+	Checkpoint { x: f32, y: f32 },
+	// the map restarted.
+	Reset,
+	// the entity spawned from `tile` was removed from (x, y), e.g. a coin picked up or an enemy stomped.
+	Removed { tile: char, x: f32, y: f32 },
+	Exit,
+	// a map failed to load, holds the command to retry once the file is fixed.
+	Failed(MapError, Command),
+}
+
+impl Event {
+
+	// true if the run can't go on in this world, later commands of the tick are dropped.
+	pub fn ends_play(&self) -> bool {
+		matches!(self, Event::Won { .. } | Event::Exit | Event::Failed(..))
+	}
+}
+
+// What a respawn puts back: the spawn, the entities taken by then and what the player had.
+// Entities are told apart by the tile they were spawned from, so they are still found after the
+// map was edited and reloaded.
+#[derive(Clone)]
+struct Snapshot {
+	spawn: (f32, f32),
+	removed: Vec<Origin>,
+	coins: i32,
+	has_gravity: bool,
+}
+
+impl Snapshot {
+
+	// the map as it is entered, with all its entities.
+	fn entered(spawn: (f32, f32), coins: i32) -> Self {
+		Snapshot { spawn, removed: Vec::new(), coins, has_gravity: true }
+	}
+}
+
+// One run through the maps: the current map, its entities and the player. Doesn't draw or
+// read the keyboard, so the game, the headless runner and tests all simulate it the same way.
+pub struct World {
+	tile_set: Rc<TileSet>,
+	// file name of the current map in maps/.
+	pub map_name: String,
+	// text of the current map when it isn't read from its file, e.g. while play-testing unsaved
+	// changes from the editor. A portal back into the same map parses it instead of the file.
+	source: Option<String>,
+	pub map: Map,
+	pub entities: Entities,
+	pub player: Player,
+	// the current map before its entities were spawned, they are spawned from it again on respawns.
+	start: Map,
+	// the map as it was entered, restarting the level goes back to it.
+	entered: Snapshot,
+	// the map as it was at the last checkpoint reached, dying goes back to it.
+	checkpoint: Option<Snapshot>,
+	// ticks left until the respawn while the player is dead, 0 while alive.
+	dying: u32,
+	// deaths since the world was loaded.
+	pub deaths: u32,
+}
+
+impl World {
+
+	// how long the player falls off the map after dying before respawning.
+	pub const DEATH_TICKS: u32 = 60;
+
+	pub fn load(map_name: &str, tile_set: Rc<TileSet>) -> Result<Self, MapError> {
+		let map = Map::from_file(&map_file(map_name), tile_set.clone())?;
+		Ok(Self::from_map(map_name, map, tile_set))
+	}
+
+	// a world on a map that is already loaded, entities are spawned from it.
+	pub fn from_map(map_name: &str, mut map: Map, tile_set: Rc<TileSet>) -> Self {
+		let start = map.clone();
+		let entities = Entities::spawn(&mut map);
+		let player = Player::new();
+		World {
+			tile_set,
+			map_name: map_name.to_string(),
+			source: None,
+			map,
+			entities,
+			entered: Snapshot::entered((player.spawn_x, player.spawn_y), 0),
+			player,
+			start,
+			checkpoint: None,
+			dying: 0,
+			deaths: 0,
+		}
+	}
+
+	// a world on the map in `source` instead of its file, until another map is loaded.
+	pub fn from_source(map_name: &str, source: &str, tile_set: Rc<TileSet>) -> Result<Self, MapError> {
+		let map = Map::parse_map(source, tile_set.clone())?;
+		let mut world = Self::from_map(map_name, map, tile_set);
+		world.source = Some(source.to_string());
+		Ok(world)
+	}
+
+	/*
+	 * Advance one tick with the keys held down and commands queued outside of the tick. While
+	 * the player is dead the keys are ignored and the entities stand still until the respawn.
+	 * */
+	pub fn tick(&mut self, input: Input, mut commands: Vec<Command>) -> Vec<Event> {
+		let mut events = Vec::new();
+		if self.dying > 0 {
+			self.dying -= 1;
+			if self.dying == 0 {
+				self.respawn();
+				events.push(Event::Respawned);
+			} else {
+				self.player.update_dead();
+			}
+		} else {
+			commands.extend(input.apply(&mut self.player, &self.map));
+			self.player.update(&self.map);
+			commands.extend(self.entities.update(&self.map, &mut self.player));
+		}
+
+		let mut checkpointed = false;
+		for command in commands {
+			if let Some(event) = self.execute(command) {
+				checkpointed |= matches!(event, Event::Checkpoint { .. });
+				// nothing else happens to a player that just died.
+				let ends_tick = event.ends_play() || matches!(event, Event::Died { .. });
+				events.push(event);
+				if ends_tick {
+					break;
+				}
+			}
+		}
+
+		// a checkpoint also keeps what the commands after it took in the same tick.
+		if checkpointed {
+			self.checkpoint = self.checkpoint.as_ref().map(|checkpoint| self.snapshot(checkpoint.spawn));
+		}
+		events
+	}
+
+	// where the last checkpoint on this map was reached, the player respawns there.
+	pub fn checkpoint(&self) -> Option<(f32, f32)> {
+		self.checkpoint.as_ref().map(|checkpoint| checkpoint.spawn)
+	}
+
+	// start the level at (x, y) from now on and put the player there.
+	pub fn set_spawn(&mut self, spawn: (f32, f32)) {
+		self.entered.spawn = spawn;
+		self.restart();
+	}
+
+	// true while the player is dead and waiting to respawn.
+	pub fn is_dying(&self) -> bool {
+		self.dying > 0
+	}
+
+	pub fn execute(&mut self, command: Command) -> Option<Event> {
+		match command {
+			Command::RemoveEntity(id) => {
+				let removed = self.entities.get(id).map(|entity| (entity.tile(), entity.position()));
+				self.entities.remove(id);
+				removed.map(|(tile, (x, y))| Event::Removed { tile, x, y })
+			}
+			Command::LoadMap(file_name, exit_portal) => {
+				if !map_exists(&file_name) {
+					return Some(Event::Won { portal: exit_portal, map: file_name });
+				}
+				if let Err(err) = self.load_map(&file_name) {
+					return Some(Event::Failed(err, Command::LoadMap(file_name, exit_portal)));
+				}
+				let spawn = self.entities.find(exit_portal).unwrap_or((Map::TILE_SIZE, Map::TILE_SIZE));
+				self.set_spawn(spawn);
+				Some(Event::EnteredPortal { portal: exit_portal, map: file_name })
+			}
+			Command::ResetMap() => {
+				self.restart();
+				Some(Event::Reset)
+			}
+			Command::ReloadMap() => {
+				let map_name = self.map_name.clone();
+				if let Err(err) = self.load_map(&map_name) {
+					return Some(Event::Failed(err, Command::ReloadMap()));
+				}
+				Some(Event::Reset)
+			}
+			Command::Die(tile, tile_x, tile_y) => {
+				if self.dying > 0 {
+					return None;
+				}
+				self.player.die();
+				self.dying = Self::DEATH_TICKS;
+				self.deaths += 1;
+				Some(Event::Died { tile, tile_x, tile_y })
+			}
+			Command::Checkpoint(id) => {
+				let (x, y) = self.entities.get(id)?.position();
+				self.player.set_spawn_pos((x, y));
+				self.checkpoint = Some(self.snapshot((x, y)));
+				Some(Event::Checkpoint { x, y })
+			}
+			Command::Exit() => Some(Event::Exit),
+		}
+	}
+
+	/*
+	 * Read the current map again after its file changed, e.g. edited beside the running game.
+	 * The player stays where it is if it still fits there, otherwise it goes back to the spawn.
+	 * Entities taken so far stay taken if they are still on the map, a checkpoint that isn't
+	 * is forgotten. Returns whether the player stayed.
+	 * */
+	pub fn reload(&mut self) -> Result<bool, MapError> {
+		self.reload_from(&map_file(&self.map_name))
+	}
+
+	// like `reload` with the map read from `path` instead of its file in maps/.
+	pub fn reload_from(&mut self, path: &str) -> Result<bool, MapError> {
+		let map = Map::from_file(path, self.tile_set.clone())?;
+		let kept = !self.is_dying() && map.is_free(self.player.bounds(), map.solids());
+		if let Some((x, y)) = self.checkpoint() {
+			let tile = map.tiles.get((y / Map::TILE_SIZE) as usize).and_then(|row| row.get((x / Map::TILE_SIZE) as usize));
+			if tile.is_none_or(|&tile| map.tile_set().touch(tile) != Some(Touch::Checkpoint)) {
+				self.checkpoint = None;
+				self.player.set_spawn_pos(self.entered.spawn);
+			}
+		}
+		self.start = map;
+		self.source = None;
+		if kept {
+			let removed = self.entities.removed();
+			self.map = self.start.clone();
+			self.entities = Entities::spawn(&mut self.map);
+			for origin in removed {
+				self.entities.remove_spawned(origin);
+			}
+		} else {
+			self.respawn();
+		}
+		Ok(kept)
+	}
+
+	// the map and the player as they are now, respawning at `spawn`.
+	fn snapshot(&self, spawn: (f32, f32)) -> Snapshot {
+		Snapshot {
+			spawn,
+			removed: self.entities.removed(),
+			coins: self.player.coins(),
+			has_gravity: self.player.has_gravity(),
+		}
+	}
+
+	// put the map back to the last checkpoint, or as it was entered, and the player at its spawn.
+	fn respawn(&mut self) {
+		let snapshot = self.checkpoint.clone().unwrap_or_else(|| self.entered.clone());
+		self.map = self.start.clone();
+		self.entities = Entities::spawn(&mut self.map);
+		for &origin in &snapshot.removed {
+			self.entities.remove_spawned(origin);
+		}
+		self.player.coins = snapshot.coins;
+		self.player.set_spawn_pos(snapshot.spawn);
+		self.player.reset();
+		if !snapshot.has_gravity {
+			self.player.g = 0.0;
+		}
+		self.dying = 0;
+	}
+
+	// start the level over as it was entered, checkpoints reached are forgotten.
+	fn restart(&mut self) {
+		self.checkpoint = None;
+		self.respawn();
+	}
+
+	// read `map_name`, from the play-tested text if it is the current map, and start it over.
+	fn load_map(&mut self, map_name: &str) -> Result<(), MapError> {
+		let map = match &self.source {
+			Some(source) if map_name == self.map_name => Map::parse_map(source, self.tile_set.clone())?,
+			_ => {
+				let map = Map::from_file(&map_file(map_name), self.tile_set.clone())?;
+				self.source = None;
+				map
+			}
+		};
+		self.start = map;
+		self.entered = Snapshot::entered((self.player.spawn_x, self.player.spawn_y), self.player.coins());
+		self.map_name = map_name.to_string();
+		self.restart();
+		Ok(())
+	}
+}
+
+// Where a new game starts.
+pub const FIRST_MAP: &str = "map1.txt";
+
+pub fn map_file(file_name: &str) -> String {
+	format!("maps/{}", file_name)
+}
+
+pub fn map_exists(file_name: &str) -> bool {
+	let path_string = map_file(file_name);
+	Path::new(&path_string).exists()
+}
+
+// file names of the maps in maps/, sorted.
+pub fn map_names() -> Vec<String> {
+	let Ok(entries) = std::fs::read_dir(map_file("")) else {
+		return Vec::new();
+	};
+	let mut names: Vec<String> = entries
+		.flatten()
+		.map(|entry| entry.path())
+		.filter(|path| path.is_file() && path.extension().is_some_and(|extension| extension == "txt"))
+		.filter_map(|path| path.file_name()?.to_str().map(String::from))
+		.collect();
+	names.sort();
+	names
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn load(name: &str) -> World {
+		let tile_set = Rc::new(TileSet::from_file(TileSet::PATH).unwrap());
+		World::load(name, tile_set).unwrap()
+	}
+
+	#[test]
+	fn test_fire_kills_and_respawns() {
+		let mut world = load("map3.txt");
+
+		// the coin above the fire on row 8 is collected, then the fire below it kills.
+		world.player.x = 10.0 * Map::TILE_SIZE;
+		world.player.y = 8.0 * Map::TILE_SIZE;
+		let mut events = Vec::new();
+		for _ in 0..30 {
+			events.extend(world.tick(Input::default(), Vec::new()));
+			if events.iter().any(|event| matches!(event, Event::Died { .. })) {
+				break;
+			}
+		}
+		assert_eq!(events, vec![
+			Event::Removed { tile: 'c', x: 10.0 * Map::TILE_SIZE, y: 8.0 * Map::TILE_SIZE },
+			Event::Died { tile: 'f', tile_x: 10, tile_y: 9 },
+		]);
+		assert_eq!(world.player.coins(), 1);
+		assert!(world.player.is_dead());
+		assert_eq!(world.deaths, 1);
+
+		// input is ignored until the respawn, which puts the coin back on the map and off the count.
+		for _ in 1..World::DEATH_TICKS {
+			assert!(world.tick(Input(Input::RESET), Vec::new()).is_empty());
+		}
+		assert_eq!(world.tick(Input::default(), Vec::new()), vec![Event::Respawned]);
+		assert!(!world.is_dying());
+		assert_eq!(world.player.coins(), 0);
+		assert!(world.entities.iter().any(|(_, entity)| entity.position() == (10.0 * Map::TILE_SIZE, 8.0 * Map::TILE_SIZE)));
+		assert_eq!((world.player.x, world.player.y), (Map::TILE_SIZE, Map::TILE_SIZE));
+	}
+
+	#[test]
+	fn test_portal_loads_next_map() {
+		let mut world = load("map1.txt");
+
+		// walk right into the 'p' portal of map1 at column 38, row 6.
+		world.player.x = 35.0 * Map::TILE_SIZE;
+		world.player.y = 6.0 * Map::TILE_SIZE;
+		let mut events = Vec::new();
+		for _ in 0..30 {
+			events = world.tick(Input(Input::RIGHT), Vec::new());
+			if !events.is_empty() {
+				break;
+			}
+		}
+		assert_eq!(events, vec![Event::EnteredPortal { portal: 'p', map: String::from("map2.txt") }]);
+		assert_eq!(world.map_name, "map2.txt");
+		assert_eq!(Some((world.player.x, world.player.y)), world.entities.find('p'));
+	}
+
+	#[test]
+	fn test_checkpoint_keeps_coins_before_it() {
+		let tile_set = Rc::new(TileSet::from_file(TileSet::PATH).unwrap());
+		let mut world = World::from_source("map1.txt", "xxxxxxx\nx ckcfx\nxxxxxxx\n", tile_set).unwrap();
+		let mut events = Vec::new();
+		for _ in 0..60 {
+			events.extend(world.tick(Input(Input::RIGHT), Vec::new()));
+			if world.is_dying() {
+				break;
+			}
+		}
+		assert!(events.contains(&Event::Checkpoint { x: 3.0 * Map::TILE_SIZE, y: Map::TILE_SIZE }));
+		assert_eq!(world.checkpoint(), Some((3.0 * Map::TILE_SIZE, Map::TILE_SIZE)));
+		assert_eq!(world.player.coins(), 2);
+
+		// the coin before the checkpoint stays collected, the one after it is back.
+		for _ in 0..World::DEATH_TICKS {
+			world.tick(Input::default(), Vec::new());
+		}
+		assert_eq!((world.player.x, world.player.y), (3.0 * Map::TILE_SIZE, Map::TILE_SIZE));
+		assert_eq!(world.player.coins(), 1);
+		let coins: Vec<(f32, f32)> = world.entities.iter()
+			.filter(|(_, entity)| entity.tile() == 'c')
+			.map(|(_, entity)| entity.position())
+			.collect();
+		assert_eq!(coins, vec![(4.0 * Map::TILE_SIZE, Map::TILE_SIZE)]);
+
+		// restarting the level goes back to how it was entered, not to the checkpoint.
+		world.execute(Command::ResetMap());
+		assert_eq!(world.checkpoint(), None);
+		assert_eq!((world.player.x, world.player.y), (Map::TILE_SIZE, Map::TILE_SIZE));
+		assert_eq!(world.player.coins(), 0);
+		assert_eq!(world.entities.iter().filter(|(_, entity)| entity.tile() == 'c').count(), 2);
+	}
+
+	#[test]
+	fn test_checkpoint_keeps_coin_taken_with_it() {
+		let tile_set = Rc::new(TileSet::from_file(TileSet::PATH).unwrap());
+		let mut world = World::from_source("map1.txt", "xxxxxx\nx   kx\nx   cx\nxxxxxx\n", tile_set).unwrap();
+
+		// sliding between the rows touches the checkpoint and the coin below it in the same tick.
+		world.player.g = 0.0;
+		world.player.y = 1.5 * Map::TILE_SIZE;
+		let mut events = Vec::new();
+		while world.checkpoint().is_none() {
+			events = world.tick(Input(Input::RIGHT), Vec::new());
+		}
+		assert!(events.iter().any(|event| matches!(event, Event::Removed { tile: 'c', .. })));
+
+		world.execute(Command::Die('f', 0, 0));
+		for _ in 0..World::DEATH_TICKS {
+			world.tick(Input::default(), Vec::new());
+		}
+		assert_eq!(world.player.coins(), 1);
+		assert_eq!(world.entities.iter().filter(|(_, entity)| entity.tile() == 'c').count(), 0);
+	}
+
+	#[test]
+	fn test_reload_keeps_taken_entities_by_tile() {
+		let path = std::env::temp_dir().join(format!("xrunner-reload-{}.txt", std::process::id()));
+		let path = path.to_str().unwrap();
+		std::fs::write(path, "xxxxxxxx\nx ck c x\nxxxxxxxx\n").unwrap();
+		let tile_set = Rc::new(TileSet::from_file(TileSet::PATH).unwrap());
+		let mut world = World::from_map("map1.txt", Map::from_file(path, tile_set.clone()).unwrap(), tile_set);
+		let id = |world: &World, tile: char, x: f32| world.entities.iter()
+			.find(|(_, entity)| entity.tile() == tile && entity.position().0 == x * Map::TILE_SIZE)
+			.map(|(id, _)| id)
+			.unwrap();
+		world.execute(Command::RemoveEntity(id(&world, 'c', 2.0)));
+		world.execute(Command::Checkpoint(id(&world, 'k', 3.0)));
+		assert!(world.checkpoint().is_some());
+
+		// a coin added in front changes every slot, the checkpoint is gone.
+		std::fs::write(path, "xxxxxxxx\nxcc  c x\nxxxxxxxx\n").unwrap();
+		let reloaded = world.reload_from(path);
+		std::fs::remove_file(path).unwrap();
+		assert_eq!(reloaded, Ok(true));
+		let coins: Vec<f32> = world.entities.iter().map(|(_, entity)| entity.position().0 / Map::TILE_SIZE).collect();
+		assert_eq!(coins, vec![1.0, 5.0]);
+		assert_eq!(world.checkpoint(), None);
+		assert_eq!((world.player.spawn_x, world.player.spawn_y), (Map::TILE_SIZE, Map::TILE_SIZE));
+	}
+
+	#[test]
+	fn test_restart_from_source() {
+		let tile_set = Rc::new(TileSet::from_file(TileSet::PATH).unwrap());
+		let mut world = World::from_source("map1.txt", "xxxx\nx cx\nxxxx\n", tile_set).unwrap();
+		world.execute(Command::ResetMap());
+		assert_eq!(world.map.width, 4);
+
+		// other maps come from their files.
+		world.execute(Command::LoadMap(String::from("map2.txt"), 'p'));
+		world.execute(Command::LoadMap(String::from("map1.txt"), 'p'));
+		assert_eq!(world.map.width, 40);
+	}
+
+	#[test]
+	fn test_reload_keeps_position() {
+		let mut world = load("map1.txt");
+		world.player.x = 5.0 * Map::TILE_SIZE;
+		world.player.y = 3.0 * Map::TILE_SIZE;
+		assert_eq!(world.reload(), Ok(true));
+		assert_eq!((world.player.x, world.player.y), (5.0 * Map::TILE_SIZE, 3.0 * Map::TILE_SIZE));
+
+		// inside the outer wall it goes back to the spawn.
+		world.player.x = 0.0;
+		assert_eq!(world.reload(), Ok(false));
+		assert_eq!((world.player.x, world.player.y), (Map::TILE_SIZE, Map::TILE_SIZE));
+	}
+
+	#[test]
+	fn test_retry_failed_load() {
+		let tile_set = Rc::new(TileSet::from_file(TileSet::PATH).unwrap());
+		let mut world = World::from_map("missing.txt", Map::empty(), tile_set.clone());
+		assert!(matches!(world.execute(Command::ReloadMap()), Some(Event::Failed(MapError::MissingFile { .. }, Command::ReloadMap()))));
+
+		// once the file is there the retry reads it instead of respawning on the empty map.
+		let mut world = World::from_map("map1.txt", Map::empty(), tile_set);
+		assert_eq!(world.execute(Command::ReloadMap()), Some(Event::Reset));
+		assert_eq!(world.map.width, 40);
+		world.execute(Command::ResetMap());
+		assert_eq!(world.map.width, 40);
+	}
+
+	#[test]
+	fn test_map_names() {
+		assert_eq!(map_names(), vec!["map1.txt", "map2.txt", "map3.txt", "map4.txt"]);
+	}
+
+	#[test]
+	fn test_missing_map_wins() {
+		let mut world = load("map3.txt");
+		let events = world.tick(Input::default(), vec![Command::LoadMap(String::from("win"), 'q'), Command::ResetMap()]);
+		assert_eq!(events, vec![Event::Won { portal: 'q', map: String::from("win") }]);
+	}
+}